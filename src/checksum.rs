@@ -0,0 +1,300 @@
+//! Module containing the verification of manufacturer-specific checksums
+
+/// Manufacturer ID of Kawai
+pub const KAWAI_ID: u8 = 0x40;
+
+/// Manufacturer ID of Roland
+pub const ROLAND_ID: u8 = 0x41;
+
+/// Manufacturer ID of Korg
+pub const KORG_ID: u8 = 0x42;
+
+/// Manufacturer ID of Yamaha
+pub const YAMAHA_ID: u8 = 0x43;
+
+/// Roland command ID for data requests
+pub const ROLAND_RQ1: u8 = 0x11;
+
+/// Roland command ID for data sets
+pub const ROLAND_DT1: u8 = 0x12;
+
+/// Offset added to the data sum by Kawai devices
+const KAWAI_CHECKSUM_OFFSET: u8 = 0xA5;
+
+/// Checksum formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Roland DT1/RQ1, sum of address and data
+    Roland,
+
+    /// Yamaha bulk dump, two's complement of the data sum
+    Yamaha,
+
+    /// Kawai one data dump, data sum plus offset
+    Kawai,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Roland => "Roland",
+                Self::Yamaha => "Yamaha",
+                Self::Kawai => "Kawai",
+            }
+        )
+    }
+}
+
+/// Result of a checksum verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Packet format is not recognized or has no checksum
+    Unknown,
+
+    /// Checksum matches
+    Valid(Format),
+
+    /// Checksum does not match
+    Invalid {
+        /// Recognized format
+        format: Format,
+
+        /// Calculated checksum
+        expected: u8,
+
+        /// Checksum found in the packet
+        found: u8,
+    },
+}
+
+/// Verify the checksum of a complete packet including start and end byte
+///
+/// Korg is out of scope: its dump formats don't carry a checksum, so the
+/// packets are reported as unknown.
+pub fn verify(packet: &[u8]) -> Status {
+    if packet.len() < 4 || packet[packet.len() - 1] != crate::midi::SYSEX_END_BYTE {
+        return Status::Unknown;
+    }
+
+    match packet[1] {
+        ROLAND_ID => verify_roland(packet),
+        YAMAHA_ID => verify_yamaha(packet),
+        KAWAI_ID => verify_kawai(packet),
+        // No checksum in Korg dumps
+        KORG_ID => Status::Unknown,
+        _ => Status::Unknown,
+    }
+}
+
+/// Return the Roland checksum for address and data bytes
+pub fn roland_checksum(bytes: &[u8]) -> u8 {
+    twos_complement(bytes)
+}
+
+/// Return the offset of the command byte in a Roland packet
+///
+/// The model ID follows the device ID and can be extended by leading zero bytes.
+pub fn roland_command_offset(packet: &[u8]) -> Option<usize> {
+    if packet.len() < 4 || packet[1] != ROLAND_ID {
+        return None;
+    }
+
+    let model_end = packet.iter().skip(3).position(|byte| *byte != 0x00)? + 3;
+    let offset = model_end + 1;
+
+    match packet.get(offset) {
+        Some(&ROLAND_DT1) | Some(&ROLAND_RQ1) => Some(offset),
+        _ => None,
+    }
+}
+
+/// Verify a Roland DT1/RQ1 packet
+fn verify_roland(packet: &[u8]) -> Status {
+    let Some(command_offset) = roland_command_offset(packet) else {
+        return Status::Unknown;
+    };
+
+    let checksum_offset = packet.len() - 2;
+    if checksum_offset <= command_offset + 1 {
+        return Status::Unknown;
+    }
+
+    check(
+        Format::Roland,
+        roland_checksum(&packet[command_offset + 1..checksum_offset]),
+        packet[checksum_offset],
+    )
+}
+
+/// Verify a Yamaha bulk dump packet
+///
+/// Depending on the model, the checksum covers the data only or also the
+/// byte count and address, so both variants are accepted.
+fn verify_yamaha(packet: &[u8]) -> Status {
+    // F0 43 0n ff bh bl <data> cs F7
+    if packet.len() < 9 || packet[2] & 0xF0 != 0x00 {
+        return Status::Unknown;
+    }
+
+    let byte_count = ((packet[4] as usize) << 7) | packet[5] as usize;
+    let checksum_offset = packet.len() - 2;
+    if checksum_offset != 6 + byte_count {
+        return Status::Unknown;
+    }
+
+    let found = packet[checksum_offset];
+    let expected = twos_complement(&packet[6..checksum_offset]);
+    if twos_complement(&packet[4..checksum_offset]) == found {
+        return Status::Valid(Format::Yamaha);
+    }
+
+    check(Format::Yamaha, expected, found)
+}
+
+/// Verify a Kawai K1/K4 one data dump packet
+fn verify_kawai(packet: &[u8]) -> Status {
+    // F0 40 0n 20 00 mm s1 s2 <data> cs F7
+    if packet.len() < 11 || packet[3] != 0x20 || packet[4] != 0x00 {
+        return Status::Unknown;
+    }
+
+    if !matches!(packet[5], 0x03 | 0x04) {
+        return Status::Unknown;
+    }
+
+    let checksum_offset = packet.len() - 2;
+    let sum = packet[8..checksum_offset]
+        .iter()
+        .fold(KAWAI_CHECKSUM_OFFSET, |sum, byte| sum.wrapping_add(*byte));
+
+    check(Format::Kawai, sum & 0x7F, packet[checksum_offset])
+}
+
+/// Return the value that makes the 7-bit sum of the bytes zero
+fn twos_complement(bytes: &[u8]) -> u8 {
+    let sum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    0x80u8.wrapping_sub(sum & 0x7F) & 0x7F
+}
+
+/// Compare the checksums and return the status
+fn check(format: Format, expected: u8, found: u8) -> Status {
+    if expected == found {
+        Status::Valid(format)
+    } else {
+        Status::Invalid {
+            format,
+            expected,
+            found,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Roland GS reset, a DT1 packet with checksum 0x41
+    const ROLAND_GS_RESET: [u8; 11] = [
+        0xF0, 0x41, 0x10, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7,
+    ];
+
+    /// Return the packet with the checksum increased by one
+    fn corrupt(packet: &[u8]) -> Vec<u8> {
+        let mut packet = packet.to_vec();
+        let offset = packet.len() - 2;
+        packet[offset] = (packet[offset] + 1) & 0x7F;
+        packet
+    }
+
+    #[test]
+    fn roland_valid() {
+        assert_eq!(verify(&ROLAND_GS_RESET), Status::Valid(Format::Roland));
+    }
+
+    #[test]
+    fn roland_corrupt() {
+        assert_eq!(
+            verify(&corrupt(&ROLAND_GS_RESET)),
+            Status::Invalid {
+                format: Format::Roland,
+                expected: 0x41,
+                found: 0x42,
+            }
+        );
+    }
+
+    #[test]
+    fn roland_checksum_wraps() {
+        // Sum 0x80 is zero in 7 bits
+        assert_eq!(roland_checksum(&[0x40, 0x40]), 0x00);
+        assert_eq!(roland_checksum(&[0x7F]), 0x01);
+        assert_eq!(roland_checksum(&[]), 0x00);
+    }
+
+    #[test]
+    fn roland_extended_model_id() {
+        // F0 41 dev 00 00 model DT1 addr data cs F7
+        let packet = [
+            0xF0, 0x41, 0x10, 0x00, 0x00, 0x2B, 0x12, 0x01, 0x00, 0x00, 0x05, 0x7A, 0xF7,
+        ];
+        assert_eq!(roland_command_offset(&packet), Some(6));
+        assert_eq!(verify(&packet), Status::Valid(Format::Roland));
+    }
+
+    #[test]
+    fn yamaha_data_checksum() {
+        let packet = [0xF0, 0x43, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02, 0x7D, 0xF7];
+        assert_eq!(verify(&packet), Status::Valid(Format::Yamaha));
+        assert_eq!(
+            verify(&corrupt(&packet)),
+            Status::Invalid {
+                format: Format::Yamaha,
+                expected: 0x7D,
+                found: 0x7E,
+            }
+        );
+    }
+
+    #[test]
+    fn yamaha_checksum_with_byte_count() {
+        let packet = [0xF0, 0x43, 0x00, 0x00, 0x00, 0x02, 0x01, 0x02, 0x7B, 0xF7];
+        assert_eq!(verify(&packet), Status::Valid(Format::Yamaha));
+    }
+
+    #[test]
+    fn yamaha_wrong_byte_count() {
+        let packet = [0xF0, 0x43, 0x00, 0x00, 0x00, 0x03, 0x01, 0x02, 0x7D, 0xF7];
+        assert_eq!(verify(&packet), Status::Unknown);
+    }
+
+    #[test]
+    fn kawai_valid() {
+        let packet = [
+            0xF0, 0x40, 0x00, 0x20, 0x00, 0x04, 0x00, 0x00, 0x01, 0x02, 0x28, 0xF7,
+        ];
+        assert_eq!(verify(&packet), Status::Valid(Format::Kawai));
+        assert_eq!(
+            verify(&corrupt(&packet)),
+            Status::Invalid {
+                format: Format::Kawai,
+                expected: 0x28,
+                found: 0x29,
+            }
+        );
+    }
+
+    #[test]
+    fn korg_not_verified() {
+        let packet = [0xF0, 0x42, 0x30, 0x19, 0x40, 0x00, 0x01, 0xF7];
+        assert_eq!(verify(&packet), Status::Unknown);
+    }
+
+    #[test]
+    fn incomplete_packet() {
+        assert_eq!(verify(&ROLAND_GS_RESET[..10]), Status::Unknown);
+        assert_eq!(verify(&[0xF0, 0x41, 0xF7]), Status::Unknown);
+    }
+}
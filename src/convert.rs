@@ -0,0 +1,136 @@
+//! Module containing the conversion between raw SysEx and Standard MIDI files
+
+use anyhow::Result;
+use midly::num::{u15, u24, u28};
+
+/// Ticks per quarter note in exported files
+const TICKS_PER_BEAT: u16 = 500;
+
+/// Tempo in microseconds per quarter note, so that one tick equals one millisecond
+const TEMPO: u32 = TICKS_PER_BEAT as u32 * 1000;
+
+/// Return the raw SysEx data of all packets
+pub fn to_syx(packets: &[Vec<u8>]) -> Vec<u8> {
+    packets.concat()
+}
+
+/// Return a type-0 Standard MIDI file containing the packets
+///
+/// Consecutive packets are placed `spacing` apart, rounded to whole milliseconds.
+pub fn to_smf(packets: &[Vec<u8>], spacing: std::time::Duration) -> Result<Vec<u8>> {
+    let millis = u32::try_from((spacing.as_micros() + 500) / 1000)?;
+    let delta = u28::try_from(millis).unwrap_or(u28::max_value());
+
+    let mut track = vec![midly::TrackEvent {
+        delta: 0.into(),
+        kind: midly::TrackEventKind::Meta(midly::MetaMessage::Tempo(u24::new(TEMPO))),
+    }];
+
+    for (index, packet) in packets.iter().enumerate() {
+        track.push(midly::TrackEvent {
+            delta: if index == 0 { 0.into() } else { delta },
+            // Start byte is implied by the event type
+            kind: midly::TrackEventKind::SysEx(&packet[1..]),
+        });
+    }
+
+    track.push(midly::TrackEvent {
+        delta,
+        kind: midly::TrackEventKind::Meta(midly::MetaMessage::EndOfTrack),
+    });
+
+    let mut smf = midly::Smf::new(midly::Header::new(
+        midly::Format::SingleTrack,
+        midly::Timing::Metrical(u15::new(TICKS_PER_BEAT)),
+    ));
+    smf.tracks.push(track);
+
+    let mut data = Vec::new();
+    smf.write_std(&mut data)?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Contents of a parsed file
+    struct Parsed {
+        /// Timing of the header
+        timing: midly::Timing,
+
+        /// Tempo in microseconds per quarter note
+        tempo: Option<u32>,
+
+        /// SysEx events with their absolute time in ticks
+        sysex: Vec<(u32, Vec<u8>)>,
+
+        /// Time of the end of the track in ticks
+        end: u32,
+    }
+
+    /// Parse a file written by `to_smf`
+    fn events(data: &[u8]) -> Parsed {
+        let smf = midly::Smf::parse(data).unwrap();
+        assert_eq!(smf.header.format, midly::Format::SingleTrack);
+        assert_eq!(smf.tracks.len(), 1);
+
+        let mut time = 0;
+        let mut tempo = None;
+        let mut sysex = Vec::new();
+        for event in &smf.tracks[0] {
+            time += event.delta.as_int();
+            match event.kind {
+                midly::TrackEventKind::Meta(midly::MetaMessage::Tempo(value)) => {
+                    tempo = Some(value.as_int())
+                }
+                midly::TrackEventKind::SysEx(bytes) => sysex.push((time, bytes.to_vec())),
+                _ => {}
+            }
+        }
+
+        Parsed {
+            timing: smf.header.timing,
+            tempo,
+            sysex,
+            end: time,
+        }
+    }
+
+    #[test]
+    fn one_tick_per_millisecond() {
+        // 500 ticks per beat at 500000 µs per beat
+        assert_eq!(TEMPO / TICKS_PER_BEAT as u32, 1000);
+    }
+
+    #[test]
+    fn smf_timing() {
+        let packets = vec![vec![0xF0, 0x41, 0xF7], vec![0xF0, 0x43, 0x01, 0xF7]];
+        let data = to_smf(&packets, std::time::Duration::from_millis(250)).unwrap();
+        let parsed = events(&data);
+
+        assert_eq!(parsed.timing, midly::Timing::Metrical(u15::new(500)));
+        assert_eq!(parsed.tempo, Some(500_000));
+        assert_eq!(
+            parsed.sysex,
+            vec![(0, vec![0x41, 0xF7]), (250, vec![0x43, 0x01, 0xF7])]
+        );
+        assert_eq!(parsed.end, 500);
+    }
+
+    #[test]
+    fn spacing_rounded_to_milliseconds() {
+        let packets = vec![vec![0xF0, 0x01, 0xF7], vec![0xF0, 0x02, 0xF7]];
+        let data = to_smf(&packets, std::time::Duration::from_micros(1600)).unwrap();
+        assert_eq!(events(&data).sysex[1].0, 2);
+        let data = to_smf(&packets, std::time::Duration::from_micros(1400)).unwrap();
+        assert_eq!(events(&data).sysex[1].0, 1);
+    }
+
+    #[test]
+    fn syx_concatenated() {
+        let packets = vec![vec![0xF0, 0x01, 0xF7], vec![0xF0, 0x02, 0xF7]];
+        assert_eq!(to_syx(&packets), vec![0xF0, 0x01, 0xF7, 0xF0, 0x02, 0xF7]);
+    }
+}
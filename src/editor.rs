@@ -0,0 +1,276 @@
+//! Module containing the editor window for Roland DT1 packets
+
+use eframe::egui;
+
+use crate::roland;
+
+/// Size of the editor window
+const WINDOW_SIZE: egui::Vec2 = egui::vec2(440.0, 420.0);
+
+/// Editor state
+#[derive(Default)]
+pub struct RolandEditor {
+    /// Window is shown
+    pub open: bool,
+
+    /// Path of the file the packets were loaded from
+    source_path: Option<std::path::PathBuf>,
+
+    /// Packets including start and end byte, modified in place
+    packets: Vec<Vec<u8>>,
+
+    /// Number of address bytes used for decoding
+    address_size: usize,
+
+    /// Address as entered by the user
+    address_text: String,
+
+    /// Value to write to the address
+    value: u8,
+
+    /// Result of the last action
+    status: Option<String>,
+}
+
+/// Actions requested from the editor
+pub enum Action {
+    /// Send the packets to the device
+    Send(Vec<Vec<u8>>),
+}
+
+impl RolandEditor {
+    /// Open the editor with the packets of a file
+    pub fn open(&mut self, path: &std::path::Path, packets: &[Vec<u8>]) {
+        self.open = true;
+        self.source_path = Some(path.to_path_buf());
+        self.packets = packets.to_vec();
+        self.address_size = packets
+            .iter()
+            .find(|packet| roland::Packet::parse(packet, 3).is_some())
+            .map(|packet| roland::default_address_size(packet))
+            .unwrap_or(3);
+        self.address_text.clear();
+        self.value = 0;
+        self.status = None;
+    }
+
+    /// Show the editor window if open, returns the requested action
+    pub fn show(&mut self, ctx: &egui::Context, busy: bool) -> Option<Action> {
+        if !self.open {
+            return None;
+        }
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("roland_editor"),
+            egui::ViewportBuilder::default()
+                .with_title("Roland DT1 Editor")
+                .with_inner_size(WINDOW_SIZE),
+            |ui, _class| {
+                if ui.ctx().input(|i| i.viewport().close_requested()) {
+                    self.open = false;
+                }
+
+                egui::CentralPanel::default()
+                    .show(ui, |ui| self.ui(ui, busy))
+                    .inner
+            },
+        )
+    }
+
+    /// Contents of the editor window
+    fn ui(&mut self, ui: &mut egui::Ui, busy: bool) -> Option<Action> {
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Address bytes:");
+            ui.selectable_value(&mut self.address_size, 3, "3");
+            ui.selectable_value(&mut self.address_size, 4, "4");
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Address:");
+            ui.add(egui::TextEdit::singleline(&mut self.address_text).desired_width(100.0))
+                .on_hover_text("Address in hex notation, e.g. 40 00 7F");
+            ui.label("Value:");
+            ui.add(
+                egui::DragValue::new(&mut self.value)
+                    .range(0..=127)
+                    .hexadecimal(2, false, true),
+            );
+            if ui
+                .button("Read")
+                .on_hover_text("Read the value at the address")
+                .clicked()
+            {
+                self.read_value();
+            }
+            if ui
+                .button("Apply")
+                .on_hover_text("Write the value to the address")
+                .clicked()
+            {
+                self.apply_value();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("Repair checksums")
+                .on_hover_text("Recalculate the checksums of all packets")
+                .clicked()
+            {
+                self.repair_checksums();
+            }
+            if ui
+                .add_enabled(!busy, egui::Button::new("Send"))
+                .on_hover_text("Send the modified packets to the device")
+                .clicked()
+            {
+                action = Some(Action::Send(self.packets.clone()));
+            }
+            if ui
+                .button("Save as…")
+                .on_hover_text("Save the modified packets as new file")
+                .clicked()
+            {
+                self.save_as();
+            }
+        });
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("roland_packets")
+                .striped(true)
+                .show(ui, |ui| {
+                    for (index, bytes) in self.packets.iter().enumerate() {
+                        let Some(packet) = roland::Packet::parse(bytes, self.address_size) else {
+                            continue;
+                        };
+                        ui.label(format!("#{}", index + 1));
+                        ui.label(packet.command.to_string());
+                        let address =
+                            roland::format_address(packet.start_address(), self.address_size);
+                        if ui
+                            .link(&address)
+                            .on_hover_text("Use this address")
+                            .clicked()
+                        {
+                            self.address_text = address;
+                        }
+                        ui.label(format!("{} bytes", packet.body.len()));
+                        if packet.checksum_valid() {
+                            ui.label(
+                                egui::RichText::new("Checksum OK").color(egui::Color32::GREEN),
+                            );
+                        } else {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "Checksum 0x{:02X}, expected 0x{:02X}",
+                                    packet.checksum,
+                                    packet.calculate_checksum()
+                                ))
+                                .color(egui::Color32::RED),
+                            );
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+
+        action
+    }
+
+    /// Read the value at the entered address
+    fn read_value(&mut self) {
+        let Some(address) = roland::parse_address(&self.address_text, self.address_size) else {
+            self.status = Some("Invalid address.".to_string());
+            return;
+        };
+
+        let value = self
+            .packets
+            .iter()
+            .filter_map(|bytes| roland::Packet::parse(bytes, self.address_size))
+            .find_map(|packet| packet.get(address));
+
+        match value {
+            Some(value) => {
+                self.value = value;
+                self.status = None;
+            }
+            None => self.status = Some("Address not found in any DT1 packet.".to_string()),
+        }
+    }
+
+    /// Write the value to the entered address
+    fn apply_value(&mut self) {
+        let Some(address) = roland::parse_address(&self.address_text, self.address_size) else {
+            self.status = Some("Invalid address.".to_string());
+            return;
+        };
+
+        let mut changed = 0;
+
+        for bytes in self.packets.iter_mut() {
+            if let Some(mut packet) = roland::Packet::parse(bytes, self.address_size)
+                && packet.set(address, self.value)
+            {
+                *bytes = packet.to_bytes();
+                changed += 1;
+            }
+        }
+
+        self.status = Some(if changed > 0 {
+            format!(
+                "Set {} to 0x{:02X} in {} packet(s).",
+                roland::format_address(address, self.address_size),
+                self.value,
+                changed
+            )
+        } else {
+            "Address not found in any DT1 packet.".to_string()
+        });
+    }
+
+    /// Recalculate the checksums of all packets
+    fn repair_checksums(&mut self) {
+        let mut repaired = 0;
+
+        for bytes in self.packets.iter_mut() {
+            if let Some(packet) = roland::Packet::parse(bytes, self.address_size)
+                && !packet.checksum_valid()
+            {
+                *bytes = packet.to_bytes();
+                repaired += 1;
+            }
+        }
+
+        self.status = Some(format!("Repaired {} checksum(s).", repaired));
+    }
+
+    /// Save the packets to a file chosen by the user
+    fn save_as(&mut self) {
+        let file_name = self
+            .source_path
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .and_then(std::ffi::OsStr::to_str)
+            .map(|stem| format!("{} (edited).syx", stem))
+            .unwrap_or_else(|| "edited.syx".to_string());
+
+        let directory = self.source_path.as_ref().and_then(|path| path.parent());
+
+        if let Some(path) = crate::save_file_dialog(&crate::FileType::SysEx, directory, &file_name)
+        {
+            self.status = Some(match std::fs::write(&path, self.packets.concat()) {
+                Ok(()) => format!("Saved to {}.", path.display()),
+                Err(error) => format!("Error: {}", error),
+            });
+        }
+    }
+}
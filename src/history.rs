@@ -0,0 +1,322 @@
+//! Module containing the history of transfers
+
+use anyhow::Result;
+use eframe::egui;
+use sha2::Digest;
+
+use crate::TransferState;
+
+/// Maximum number of entries kept in the history
+pub const HISTORY_LIMIT: usize = 1000;
+
+/// Size of the history window
+const WINDOW_SIZE: egui::Vec2 = egui::vec2(720.0, 360.0);
+
+/// Format of timestamps in the window and exports
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Record of a single transfer
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Entry {
+    /// Start time of the transfer
+    pub timestamp: chrono::DateTime<chrono::Local>,
+
+    /// Path of the file
+    pub file_path: std::path::PathBuf,
+
+    /// SHA-256 hash of the file contents in hex notation
+    pub file_hash: String,
+
+    /// SHA-256 hash of the packets actually sent in hex notation
+    #[serde(default)]
+    pub packets_hash: String,
+
+    /// Packets were edited before sending, so the file doesn't reproduce them
+    #[serde(default)]
+    pub edited: bool,
+
+    /// Name of the output port
+    pub port_name: String,
+
+    /// Key of the output port, empty for entries of earlier versions
+    #[serde(default)]
+    pub port_key: String,
+
+    /// Number of packets sent
+    pub packet_count: usize,
+
+    /// Interval in ms between packets
+    pub packet_interval: u64,
+
+    /// Final state of the transfer
+    pub outcome: TransferState,
+
+    /// Error message if the transfer failed
+    pub error: Option<String>,
+}
+
+/// Return the SHA-256 hash of a file in hex notation
+pub fn file_hash(path: &std::path::Path) -> Result<String> {
+    Ok(hex_digest(&std::fs::read(path)?))
+}
+
+/// Return the SHA-256 hash of the concatenated packets in hex notation
+pub fn packets_hash(packets: &[Vec<u8>]) -> String {
+    hex_digest(&packets.concat())
+}
+
+/// Return the SHA-256 hash of data in hex notation
+fn hex_digest(data: &[u8]) -> String {
+    let digest = sha2::Sha256::digest(data);
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Return the entries as CSV with header line
+pub fn to_csv(entries: &[Entry]) -> String {
+    let mut csv = String::from(
+        "timestamp,file_path,file_hash,packets_hash,edited,port_name,port_key,packet_count,packet_interval,outcome,error\n",
+    );
+
+    for entry in entries {
+        let fields = [
+            entry.timestamp.format(TIMESTAMP_FORMAT).to_string(),
+            entry.file_path.display().to_string(),
+            entry.file_hash.clone(),
+            entry.packets_hash.clone(),
+            entry.edited.to_string(),
+            entry.port_name.clone(),
+            entry.port_key.clone(),
+            entry.packet_count.to_string(),
+            entry.packet_interval.to_string(),
+            entry.outcome.to_string(),
+            entry.error.clone().unwrap_or_default(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// Quote a CSV field if required
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// History window state
+#[derive(Default)]
+pub struct HistoryWindow {
+    /// Window is shown
+    pub open: bool,
+
+    /// Result of the last action
+    status: Option<String>,
+}
+
+/// Actions requested from the history window
+pub enum Action {
+    /// Repeat the transfer of an entry
+    Rerun(Entry),
+}
+
+impl HistoryWindow {
+    /// Show the window if open, returns the requested action
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        entries: &mut Vec<Entry>,
+        busy: bool,
+    ) -> Option<Action> {
+        if !self.open {
+            return None;
+        }
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("history_window"),
+            egui::ViewportBuilder::default()
+                .with_title("Transfer History")
+                .with_inner_size(WINDOW_SIZE),
+            |ui, _class| {
+                if ui.ctx().input(|i| i.viewport().close_requested()) {
+                    self.open = false;
+                }
+
+                egui::CentralPanel::default()
+                    .show(ui, |ui| self.ui(ui, entries, busy))
+                    .inner
+            },
+        )
+    }
+
+    /// Contents of the history window
+    fn ui(&mut self, ui: &mut egui::Ui, entries: &mut Vec<Entry>, busy: bool) -> Option<Action> {
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            if ui.button("Export CSV…").clicked() {
+                self.export(entries, "csv");
+            }
+            if ui.button("Export JSON…").clicked() {
+                self.export(entries, "json");
+            }
+            if ui
+                .add_enabled(!entries.is_empty(), egui::Button::new("Clear"))
+                .clicked()
+            {
+                entries.clear();
+            }
+        });
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        ui.separator();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("history_entries")
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("Time");
+                    ui.strong("File");
+                    ui.strong("Port");
+                    ui.strong("Packets");
+                    ui.strong("Delay");
+                    ui.strong("Outcome");
+                    ui.end_row();
+
+                    for entry in entries.iter().rev() {
+                        ui.label(entry.timestamp.format(TIMESTAMP_FORMAT).to_string());
+                        ui.label(
+                            entry
+                                .file_path
+                                .file_name()
+                                .and_then(std::ffi::OsStr::to_str)
+                                .unwrap_or("Invalid filename"),
+                        )
+                        .on_hover_text(format!(
+                            "{}\nSHA-256: {}\nPackets SHA-256: {}{}",
+                            entry.file_path.display(),
+                            entry.file_hash,
+                            entry.packets_hash,
+                            if entry.edited {
+                                "\nEdited before sending"
+                            } else {
+                                ""
+                            }
+                        ));
+                        ui.label(&entry.port_name);
+                        ui.label(entry.packet_count.to_string());
+                        ui.label(format!("{} ms", entry.packet_interval));
+                        let color = match entry.outcome {
+                            TransferState::Finished => egui::Color32::GREEN,
+                            _ => egui::Color32::RED,
+                        };
+                        let label =
+                            ui.label(egui::RichText::new(entry.outcome.to_string()).color(color));
+                        if let Some(error) = &entry.error {
+                            label.on_hover_text(error);
+                        }
+                        if ui
+                            .add_enabled(!busy && !entry.edited, egui::Button::new("Re-run"))
+                            .on_hover_text("Send the file again with the same settings")
+                            .on_disabled_hover_text(if entry.edited {
+                                "Edited packets can't be sent again from the file"
+                            } else {
+                                "A transfer is running"
+                            })
+                            .clicked()
+                        {
+                            action = Some(Action::Rerun(entry.clone()));
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+
+        action
+    }
+
+    /// Export the entries to a file chosen by the user
+    fn export(&mut self, entries: &[Entry], extension: &str) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter(extension.to_uppercase(), &[extension])
+            .set_file_name(format!("sysex-drop-history.{}", extension))
+            .save_file()
+        else {
+            return;
+        };
+
+        let data = match extension {
+            "json" => serde_json::to_string_pretty(entries).map_err(anyhow::Error::from),
+            _ => Ok(to_csv(entries)),
+        };
+
+        self.status = Some(
+            match data.and_then(|data| Ok(std::fs::write(&path, data)?)) {
+                Ok(()) => format!("Exported to {}.", path.display()),
+                Err(error) => format!("Error: {}", error),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return an entry with file path and error message
+    fn entry(file_path: &str, error: Option<&str>) -> Entry {
+        Entry {
+            timestamp: chrono::Local::now(),
+            file_path: file_path.into(),
+            file_hash: "abc".to_string(),
+            packets_hash: "def".to_string(),
+            edited: false,
+            port_name: "XYZ MIDI 1".to_string(),
+            port_key: "XYZ MIDI 1".to_string(),
+            packet_count: 2,
+            packet_interval: 20,
+            outcome: TransferState::Failed,
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn csv_field_plain() {
+        assert_eq!(csv_field("dump.syx"), "dump.syx");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn csv_field_quoted() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("line\rbreak"), "\"line\rbreak\"");
+    }
+
+    #[test]
+    fn csv_lines() {
+        let csv = to_csv(&[entry(
+            "/tmp/a,b.syx",
+            Some("No reply to packet 1.\nGiving up"),
+        )]);
+        let (header, line) = csv.split_once('\n').unwrap();
+        assert!(header.starts_with("timestamp,file_path,"));
+        assert!(header.ends_with(",outcome,error"));
+        assert!(line.contains(",\"/tmp/a,b.syx\",abc,def,false,XYZ MIDI 1,XYZ MIDI 1,2,20,"));
+        assert!(line.ends_with(",\"No reply to packet 1.\nGiving up\"\n"));
+    }
+
+    #[test]
+    fn csv_without_error() {
+        let csv = to_csv(&[entry("/tmp/dump.syx", None)]);
+        assert!(csv.ends_with(",/tmp/dump.syx,abc,def,false,XYZ MIDI 1,XYZ MIDI 1,2,20,Failed,\n"));
+    }
+}
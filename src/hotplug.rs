@@ -0,0 +1,171 @@
+//! Module containing the detection of added and removed MIDI ports
+//!
+//! On Linux, the ALSA sequencer announces port changes, so ports are scanned
+//! only when something has happened. Elsewhere, or if the announcements are not
+//! available, ports are polled with an interval that grows while nothing changes.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{Message, midi};
+
+/// Initial poll interval and interval after a change
+const POLL_INTERVAL_MIN: Duration = Duration::from_millis(250);
+
+/// Maximum poll interval while nothing changes
+const POLL_INTERVAL_MAX: Duration = Duration::from_secs(4);
+
+/// Poll interval as safety net when port notifications are available
+const POLL_INTERVAL_NOTIFIED: Duration = Duration::from_secs(10);
+
+/// Interval for checking if scanning is allowed again
+const PAUSE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Start scanning the ports in a separate thread
+///
+/// `Message::PortsChanged` is sent after a change. While `paused` is set,
+/// e.g. during a transfer, only the existing connections are checked every
+/// `PAUSE_INTERVAL` and the scan is done afterwards.
+pub fn start(
+    midi: Arc<Mutex<midi::MidiConnector>>,
+    message_sender: Sender<Message>,
+    paused: Arc<AtomicBool>,
+) {
+    let (notify_sender, notify_receiver) = std::sync::mpsc::channel();
+    let notified = start_notifications(notify_sender);
+
+    std::thread::spawn(move || {
+        scan_loop(&midi, &message_sender, &paused, &notify_receiver, notified);
+    });
+}
+
+/// Scan the ports on notifications or poll with back-off
+fn scan_loop(
+    midi: &Arc<Mutex<midi::MidiConnector>>,
+    message_sender: &Sender<Message>,
+    paused: &AtomicBool,
+    notify_receiver: &Receiver<()>,
+    notified: bool,
+) {
+    let mut interval = POLL_INTERVAL_MIN;
+    let mut pending = true;
+
+    loop {
+        let timeout = if paused.load(Ordering::Relaxed) {
+            PAUSE_INTERVAL
+        } else if pending {
+            Duration::ZERO
+        } else if notified {
+            POLL_INTERVAL_NOTIFIED
+        } else {
+            interval
+        };
+
+        let notification = match notify_receiver.recv_timeout(timeout) {
+            Ok(()) => {
+                // Several notifications usually arrive at once
+                while notify_receiver.try_recv().is_ok() {}
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                std::thread::sleep(timeout);
+                false
+            }
+        };
+
+        if paused.load(Ordering::Relaxed) {
+            // Only the connections are checked, the scan is done afterwards.
+            // Checking on timeouts as well covers platforms and backends
+            // without notifications.
+            pending |= notification;
+            if midi.lock().unwrap().check_connections() {
+                pending = true;
+                message_sender.send(Message::PortsChanged).ok();
+            }
+            continue;
+        }
+
+        // Also reported after deferred scans, so that ports are connected again
+        let deferred = std::mem::take(&mut pending);
+
+        if midi.lock().unwrap().scan_ports() || deferred {
+            interval = POLL_INTERVAL_MIN;
+            if message_sender.send(Message::PortsChanged).is_err() {
+                break;
+            }
+        } else {
+            interval = (interval * 2).min(POLL_INTERVAL_MAX);
+        }
+    }
+}
+
+/// Start receiving port notifications, returns if available
+#[cfg(target_os = "linux")]
+fn start_notifications(notify_sender: Sender<()>) -> bool {
+    let (result_sender, result_receiver) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let seq = match announce_client() {
+            Ok(seq) => {
+                result_sender.send(true).ok();
+                seq
+            }
+            Err(error) => {
+                log::warn!("ALSA port notifications not available: {}", error);
+                result_sender.send(false).ok();
+                return;
+            }
+        };
+
+        let mut input = seq.input();
+        while let Ok(event) = input.event_input() {
+            if matches!(
+                event.get_type(),
+                alsa::seq::EventType::ClientStart
+                    | alsa::seq::EventType::ClientExit
+                    | alsa::seq::EventType::PortStart
+                    | alsa::seq::EventType::PortExit
+                    | alsa::seq::EventType::PortChange
+            ) && notify_sender.send(()).is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    result_receiver.recv().unwrap_or(false)
+}
+
+/// Create a sequencer client subscribed to the system announce port
+#[cfg(target_os = "linux")]
+fn announce_client() -> alsa::Result<alsa::Seq> {
+    use alsa::seq::{Addr, PortCap, PortSubscribe, PortType};
+
+    let seq = alsa::Seq::open(None, Some(alsa::Direction::Capture), false)?;
+    seq.set_client_name(c"sysex-drop hotplug")?;
+
+    let port = seq.create_simple_port(
+        c"announce",
+        PortCap::WRITE | PortCap::SUBS_WRITE | PortCap::NO_EXPORT,
+        PortType::MIDI_GENERIC | PortType::APPLICATION,
+    )?;
+
+    let subscription = PortSubscribe::empty()?;
+    subscription.set_sender(Addr::system_announce());
+    subscription.set_dest(Addr {
+        client: seq.client_id()?,
+        port,
+    });
+    seq.subscribe_port(&subscription)?;
+
+    Ok(seq)
+}
+
+/// Start receiving port notifications, not available on this platform
+#[cfg(not(target_os = "linux"))]
+fn start_notifications(_notify_sender: Sender<()>) -> bool {
+    false
+}
@@ -0,0 +1,165 @@
+//! Module containing the single-instance mode
+//!
+//! The first instance listens on a local socket. Further instances forward
+//! their file arguments to it and exit instead of opening a second window.
+//! Without file arguments, the window of the running instance is focused.
+
+use std::sync::mpsc::Sender;
+
+use anyhow::Result;
+
+use crate::Message;
+
+/// Forward the file paths to a running instance
+///
+/// Returns `true` if an instance is running and received the paths. An
+/// empty list asks the running instance to focus its window.
+#[cfg(unix)]
+pub fn forward(paths: &[std::path::PathBuf]) -> bool {
+    use std::io::Write;
+    use std::os::unix::ffi::OsStrExt;
+
+    let Ok(socket_path) = socket_path() else {
+        return false;
+    };
+    let Ok(mut stream) = std::os::unix::net::UnixStream::connect(socket_path) else {
+        return false;
+    };
+
+    let mut data = Vec::new();
+    for path in paths {
+        // The running instance has a different working directory
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        data.extend_from_slice(path.as_os_str().as_bytes());
+        data.push(b'\n');
+    }
+
+    match stream.write_all(&data) {
+        Ok(()) if paths.is_empty() => {
+            log::info!("Running instance focused.");
+            true
+        }
+        Ok(()) => {
+            log::info!("Files forwarded to running instance.");
+            true
+        }
+        Err(error) => {
+            log::error!("Forwarding files failed: {}", error);
+            false
+        }
+    }
+}
+
+/// Forward the file paths to a running instance, not supported on this platform
+#[cfg(not(unix))]
+pub fn forward(_paths: &[std::path::PathBuf]) -> bool {
+    false
+}
+
+/// Listening socket of the first instance, removed when dropped
+#[cfg(unix)]
+pub struct Listener {
+    /// Path of the socket
+    socket_path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.socket_path).ok();
+    }
+}
+
+/// Listening socket of the first instance, not supported on this platform
+#[cfg(not(unix))]
+pub struct Listener;
+
+/// Listen for forwarded files and pass them as messages
+#[cfg(unix)]
+pub fn listen(message_sender: Sender<Message>) -> Result<Listener> {
+    use std::io::Read;
+    use std::os::unix::ffi::OsStrExt;
+
+    let socket_path = socket_path()?;
+
+    // Socket left over from an instance that did not exit cleanly,
+    // only removed if no instance is listening on it
+    if socket_path.exists() {
+        if std::os::unix::net::UnixStream::connect(&socket_path).is_ok() {
+            return Err(anyhow::anyhow!("Another instance is listening."));
+        }
+        std::fs::remove_file(&socket_path)?;
+    }
+
+    let listener = std::os::unix::net::UnixListener::bind(&socket_path)?;
+
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut data = Vec::new();
+            if let Err(error) = stream.read_to_end(&mut data) {
+                log::error!("Receiving forwarded files failed: {}", error);
+                continue;
+            }
+
+            let paths: Vec<_> = data
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.is_empty())
+                .map(|line| std::path::PathBuf::from(std::ffi::OsStr::from_bytes(line)))
+                .collect();
+
+            if paths.is_empty() {
+                message_sender.send(Message::FocusWindow).ok();
+            } else {
+                message_sender.send(Message::OpenFiles(paths)).ok();
+            }
+        }
+    });
+
+    Ok(Listener { socket_path })
+}
+
+/// Listen for forwarded files, not supported on this platform
+#[cfg(not(unix))]
+pub fn listen(_message_sender: Sender<Message>) -> Result<Listener> {
+    Ok(Listener)
+}
+
+/// Return the path of the socket, separate for each user
+#[cfg(unix)]
+fn socket_path() -> Result<std::path::PathBuf> {
+    Ok(private_directory()?.join("sysex-drop.sock"))
+}
+
+/// Return a directory only accessible by the current user
+///
+/// The runtime directory is private by definition. Otherwise a directory
+/// with mode `0700` is used in the shared temporary directory.
+#[cfg(unix)]
+fn private_directory() -> Result<std::path::PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    if let Some(directory) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(directory.into());
+    }
+
+    let user = std::env::var("USER").unwrap_or_default();
+    let directory = std::env::temp_dir().join(format!("sysex-drop-{}", user));
+
+    match std::fs::DirBuilder::new().mode(0o700).create(&directory) {
+        Ok(()) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(error) => return Err(error.into()),
+    }
+
+    if !std::fs::symlink_metadata(&directory)?.is_dir() {
+        return Err(anyhow::anyhow!(
+            "{} is not a directory.",
+            directory.display()
+        ));
+    }
+
+    // Changing the mode fails unless the directory belongs to the user
+    std::fs::set_permissions(&directory, std::fs::Permissions::from_mode(0o700))?;
+
+    Ok(directory)
+}
@@ -0,0 +1,495 @@
+//! Module containing the JACK MIDI backend
+//!
+//! The application registers as JACK client with one MIDI output and one
+//! MIDI input port. Selecting a device connects these ports, so they can be
+//! routed with the rest of a JACK session as well. Only available when built
+//! with the `jack` feature.
+
+#[cfg(feature = "jack")]
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+
+use crate::midi::InputMessage;
+
+/// Name of the JACK client
+#[cfg(feature = "jack")]
+const CLIENT_NAME: &str = "SysEx Drop";
+
+/// Type of the JACK MIDI ports
+#[cfg(feature = "jack")]
+const MIDI_TYPE: &str = "8 bit raw midi";
+
+/// Size of the ring buffers between the process callback and the other threads
+#[cfg(feature = "jack")]
+const RING_BUFFER_SIZE: usize = 65536;
+
+/// Size of the length prefix of messages in the ring buffers
+#[cfg(feature = "jack")]
+const LENGTH_SIZE: usize = std::mem::size_of::<u32>();
+
+/// Size of the timestamp prefix of incoming messages
+#[cfg(feature = "jack")]
+const TIMESTAMP_SIZE: usize = std::mem::size_of::<u64>();
+
+/// Interval for polling the ring buffers and the state
+#[cfg(feature = "jack")]
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Time to wait for the process callback taking a message
+#[cfg(feature = "jack")]
+const SEND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// State shared between the client and its callbacks
+#[cfg(feature = "jack")]
+struct State {
+    /// Client is active and the server is running
+    alive: AtomicBool,
+
+    /// Largest message that fits into the output buffer
+    max_size: AtomicUsize,
+
+    /// Number of messages taken from the outgoing ring buffer
+    taken: AtomicUsize,
+
+    /// Number of messages that could not be written to the output
+    failed: AtomicUsize,
+}
+
+/// Handler exchanging the messages in the process callback
+///
+/// Messages are passed through preallocated ring buffers, so that the
+/// callback neither allocates nor blocks.
+#[cfg(feature = "jack")]
+struct Process {
+    /// Own output port
+    output: jack::Port<jack::MidiOut>,
+
+    /// Own input port
+    input: jack::Port<jack::MidiIn>,
+
+    /// Length-prefixed messages to be written to the output
+    outgoing: jack::RingBufferReader,
+
+    /// Buffer for the message taken from the outgoing ring buffer
+    message: Box<[u8]>,
+
+    /// Timestamped and length-prefixed messages read from the input
+    incoming: jack::RingBufferWriter,
+
+    /// State shared with the client
+    state: Arc<State>,
+}
+
+#[cfg(feature = "jack")]
+impl jack::ProcessHandler for Process {
+    fn process(&mut self, client: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+        let mut writer = self.output.writer(ps);
+        self.state
+            .max_size
+            .store(writer.max_event_size(), Ordering::Relaxed);
+
+        // Only one message per cycle, so that the sender is paced by the server
+        let mut length = [0; LENGTH_SIZE];
+        if self.outgoing.peek(&mut length) == LENGTH_SIZE {
+            let size = u32::from_le_bytes(length) as usize;
+            if self.outgoing.space() >= LENGTH_SIZE + size {
+                self.outgoing.advance(LENGTH_SIZE);
+                let message = &mut self.message[..size];
+                self.outgoing.read_buffer(message);
+                let written = writer.write(&jack::RawMidi {
+                    time: 0,
+                    bytes: message,
+                });
+                if written.is_err() {
+                    self.state.failed.fetch_add(1, Ordering::Release);
+                }
+                self.state.taken.fetch_add(1, Ordering::Release);
+            }
+        }
+
+        for event in self.input.iter(ps) {
+            // Messages not fitting into the ring buffer are dropped
+            if self.incoming.space() < TIMESTAMP_SIZE + LENGTH_SIZE + event.bytes.len() {
+                continue;
+            }
+            let timestamp = client.frames_to_time(ps.last_frame_time() + event.time);
+            self.incoming.write_buffer(&timestamp.to_le_bytes());
+            self.incoming
+                .write_buffer(&(event.bytes.len() as u32).to_le_bytes());
+            self.incoming.write_buffer(event.bytes);
+        }
+
+        jack::Control::Continue
+    }
+}
+
+/// Handler for notifications of the server
+#[cfg(feature = "jack")]
+struct Notifications {
+    /// State shared with the client
+    state: Arc<State>,
+}
+
+#[cfg(feature = "jack")]
+impl jack::NotificationHandler for Notifications {
+    unsafe fn shutdown(&mut self, _status: jack::ClientStatus, _reason: &str) {
+        // Only setting a flag is allowed here
+        self.state.alive.store(false, Ordering::Release);
+    }
+}
+
+/// Registered JACK client
+#[cfg(feature = "jack")]
+pub struct Client {
+    /// Active client running the handlers
+    client: jack::AsyncClient<Notifications, Process>,
+
+    /// Full name of the own output port
+    output_name: String,
+
+    /// Full name of the own input port
+    input_name: String,
+
+    /// Writer for messages to the process handler, locked for the whole send
+    outgoing: Mutex<jack::RingBufferWriter>,
+
+    /// Space of the outgoing ring buffer when empty
+    outgoing_capacity: usize,
+
+    /// State shared with the callbacks
+    state: Arc<State>,
+}
+
+#[cfg(feature = "jack")]
+impl Client {
+    /// Register the client at a running JACK server
+    ///
+    /// Messages received on the input are passed to the subscribers.
+    pub fn new(subscribers: Arc<Mutex<Vec<Sender<InputMessage>>>>) -> Result<Arc<Self>> {
+        let (client, _status) =
+            jack::Client::new(CLIENT_NAME, jack::ClientOptions::NO_START_SERVER)
+                .map_err(|error| anyhow::anyhow!("JACK client error: {}", error))?;
+
+        let output = client.register_port("output", jack::MidiOut::default())?;
+        let input = client.register_port("input", jack::MidiIn::default())?;
+        let output_name = output.name()?;
+        let input_name = input.name()?;
+
+        let (outgoing_reader, mut outgoing) =
+            jack::RingBuffer::new(RING_BUFFER_SIZE)?.into_reader_writer();
+        let outgoing_capacity = outgoing.space();
+        let (incoming_reader, incoming) =
+            jack::RingBuffer::new(RING_BUFFER_SIZE)?.into_reader_writer();
+        let state = Arc::new(State {
+            alive: AtomicBool::new(true),
+            max_size: AtomicUsize::new(usize::MAX),
+            taken: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+        });
+
+        // Passing on is done outside of the process callback
+        let thread_state = state.clone();
+        std::thread::spawn(move || pass_incoming(incoming_reader, &subscribers, &thread_state));
+
+        let process = Process {
+            output,
+            input,
+            outgoing: outgoing_reader,
+            message: vec![0; RING_BUFFER_SIZE].into_boxed_slice(),
+            incoming,
+            state: state.clone(),
+        };
+        let notifications = Notifications {
+            state: state.clone(),
+        };
+        let client = client
+            .activate_async(notifications, process)
+            .map_err(|error| anyhow::anyhow!("JACK activation error: {}", error))?;
+
+        log::info!("JACK client {} registered.", client.as_client().name());
+
+        Ok(Arc::new(Self {
+            client,
+            output_name,
+            input_name,
+            outgoing: Mutex::new(outgoing),
+            outgoing_capacity,
+            state,
+        }))
+    }
+
+    /// Return if the server is still running
+    pub fn is_alive(&self) -> bool {
+        self.state.alive.load(Ordering::Acquire)
+    }
+
+    /// Return identifiers and names of the ports the output can be connected to
+    pub fn outputs(&self) -> Vec<(String, String)> {
+        self.ports(jack::PortFlags::IS_INPUT)
+    }
+
+    /// Return identifiers and names of the ports the input can be connected to
+    pub fn inputs(&self) -> Vec<(String, String)> {
+        self.ports(jack::PortFlags::IS_OUTPUT)
+    }
+
+    /// Return the MIDI ports of other clients with the flags
+    fn ports(&self, flags: jack::PortFlags) -> Vec<(String, String)> {
+        if !self.is_alive() {
+            return Vec::new();
+        }
+
+        let own_prefix = format!("{}:", self.client.as_client().name());
+
+        self.client
+            .as_client()
+            .ports(None, Some(MIDI_TYPE), flags)
+            .into_iter()
+            .filter(|name| !name.starts_with(&own_prefix))
+            .map(|name| (name.clone(), name))
+            .collect()
+    }
+
+    /// Return if a port exists
+    pub fn port_exists(&self, name: &str) -> bool {
+        self.is_alive() && self.client.as_client().port_by_name(name).is_some()
+    }
+
+    /// Connect the output to a port
+    pub fn connect_output(self: &Arc<Self>, target: &str) -> Result<Connection> {
+        self.connect(&self.output_name, target, true)
+    }
+
+    /// Connect a port to the input
+    pub fn connect_input(self: &Arc<Self>, source: &str) -> Result<Connection> {
+        self.connect(source, &self.input_name, false)
+    }
+
+    /// Connect two ports, returning the connection of the own port
+    fn connect(
+        self: &Arc<Self>,
+        source: &str,
+        destination: &str,
+        output: bool,
+    ) -> Result<Connection> {
+        self.client
+            .as_client()
+            .connect_ports_by_name(source, destination)
+            .map_err(|error| anyhow::anyhow!("JACK connection error: {}", error))?;
+
+        Ok(Connection {
+            client: self.clone(),
+            source: source.to_string(),
+            destination: destination.to_string(),
+            output,
+        })
+    }
+
+    /// Pass a message to the process handler, waiting until it is written
+    fn send(&self, message: &[u8]) -> Result<()> {
+        if !self.is_alive() {
+            return Err(anyhow::anyhow!("JACK server not running."));
+        }
+
+        let max_size = self
+            .state
+            .max_size
+            .load(Ordering::Relaxed)
+            .min(self.outgoing_capacity - LENGTH_SIZE);
+        if message.len() > max_size {
+            return Err(anyhow::anyhow!(
+                "Packet of {} bytes exceeds the JACK MIDI buffer of {} bytes.",
+                message.len(),
+                max_size
+            ));
+        }
+
+        let mut outgoing = self.outgoing.lock().unwrap();
+
+        // A message left over from a timed out send must be taken first
+        let deadline = std::time::Instant::now() + SEND_TIMEOUT;
+        while outgoing.space() < self.outgoing_capacity {
+            self.wait(deadline)?;
+        }
+
+        let taken = self.state.taken.load(Ordering::Acquire);
+        let failed = self.state.failed.load(Ordering::Acquire);
+        outgoing.write_buffer(&(message.len() as u32).to_le_bytes());
+        outgoing.write_buffer(message);
+
+        while self.state.taken.load(Ordering::Acquire) == taken {
+            self.wait(deadline)?;
+        }
+
+        if self.state.failed.load(Ordering::Acquire) != failed {
+            return Err(anyhow::anyhow!("Writing to the JACK MIDI output failed."));
+        }
+
+        Ok(())
+    }
+
+    /// Wait for the process handler, failing after the deadline or if the server shut down
+    fn wait(&self, deadline: std::time::Instant) -> Result<()> {
+        if !self.is_alive() {
+            return Err(anyhow::anyhow!("JACK server not running."));
+        }
+        if std::time::Instant::now() > deadline {
+            return Err(anyhow::anyhow!("JACK server not responding."));
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "jack")]
+impl Drop for Client {
+    fn drop(&mut self) {
+        // Ends the thread passing on the incoming messages
+        self.state.alive.store(false, Ordering::Release);
+    }
+}
+
+/// Pass messages from the incoming ring buffer on to the subscribers until the client ends
+#[cfg(feature = "jack")]
+fn pass_incoming(
+    mut incoming: jack::RingBufferReader,
+    subscribers: &Mutex<Vec<Sender<InputMessage>>>,
+    state: &State,
+) {
+    const HEADER_SIZE: usize = TIMESTAMP_SIZE + LENGTH_SIZE;
+
+    while state.alive.load(Ordering::Acquire) {
+        let mut header = [0; HEADER_SIZE];
+        if incoming.peek(&mut header) < HEADER_SIZE {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        let (timestamp, length) = header.split_at(TIMESTAMP_SIZE);
+        let timestamp = u64::from_le_bytes(timestamp.try_into().expect("timestamp size"));
+        let size = u32::from_le_bytes(length.try_into().expect("length size")) as usize;
+
+        // The process handler writes the whole message at once, so this is only a precaution
+        if incoming.space() < HEADER_SIZE + size {
+            std::thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+        incoming.advance(HEADER_SIZE);
+        let mut data = vec![0; size];
+        incoming.read_buffer(&mut data);
+
+        let message = InputMessage { timestamp, data };
+        subscribers
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.send(message.clone()).is_ok());
+    }
+}
+
+/// Connection between an own port and a port of another client, removed when dropped
+#[cfg(feature = "jack")]
+pub struct Connection {
+    /// Client owning the port
+    client: Arc<Client>,
+
+    /// Full name of the source port
+    source: String,
+
+    /// Full name of the destination port
+    destination: String,
+
+    /// The own port is the output
+    output: bool,
+}
+
+#[cfg(feature = "jack")]
+impl Connection {
+    /// Sends a message on the output
+    pub fn send(&mut self, message: &[u8]) -> Result<()> {
+        if !self.output {
+            return Err(anyhow::anyhow!("JACK input can not send messages."));
+        }
+
+        self.client.send(message)
+    }
+}
+
+#[cfg(feature = "jack")]
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if !self.client.is_alive() {
+            return;
+        }
+
+        self.client
+            .client
+            .as_client()
+            .disconnect_ports_by_name(&self.source, &self.destination)
+            .ok();
+    }
+}
+
+/// Registered JACK client, not included in this build
+#[cfg(not(feature = "jack"))]
+pub struct Client;
+
+#[cfg(not(feature = "jack"))]
+impl Client {
+    /// Register the client at a running JACK server
+    pub fn new(_subscribers: Arc<Mutex<Vec<Sender<InputMessage>>>>) -> Result<Arc<Self>> {
+        Err(anyhow::anyhow!(
+            "JACK support is not included in this build."
+        ))
+    }
+
+    /// Return if the server is still running
+    pub fn is_alive(&self) -> bool {
+        false
+    }
+
+    /// Return identifiers and names of the ports the output can be connected to
+    pub fn outputs(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Return identifiers and names of the ports the input can be connected to
+    pub fn inputs(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Return if a port exists
+    pub fn port_exists(&self, _name: &str) -> bool {
+        false
+    }
+
+    /// Connect the output to a port
+    pub fn connect_output(self: &Arc<Self>, _target: &str) -> Result<Connection> {
+        Err(anyhow::anyhow!(
+            "JACK support is not included in this build."
+        ))
+    }
+
+    /// Connect a port to the input
+    pub fn connect_input(self: &Arc<Self>, _source: &str) -> Result<Connection> {
+        Err(anyhow::anyhow!(
+            "JACK support is not included in this build."
+        ))
+    }
+}
+
+/// Connection between an own port and a port of another client, not included in this build
+#[cfg(not(feature = "jack"))]
+pub struct Connection;
+
+#[cfg(not(feature = "jack"))]
+impl Connection {
+    /// Sends a message on the output
+    pub fn send(&mut self, _message: &[u8]) -> Result<()> {
+        Err(anyhow::anyhow!(
+            "JACK support is not included in this build."
+        ))
+    }
+}
@@ -2,35 +2,116 @@
 #![windows_subsystem = "windows"]
 #![warn(missing_docs)]
 
+mod checksum;
+mod convert;
+mod editor;
+mod history;
+mod hotplug;
+mod instance;
+mod jackmidi;
+mod merge;
 mod midi;
-
-use std::io::{BufRead, BufReader, Seek};
+mod monitor;
+mod pacing;
+mod rawmidi;
+mod remote;
+mod report;
+mod roland;
+mod rtpmidi;
+mod split;
+mod verify;
+mod watch;
+
+use std::io::{BufRead, BufReader};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use anyhow::{Result, anyhow};
 use eframe::egui::{
-    self, FontFamily, FontId, Margin,
+    self, FontFamily, FontId, Key, KeyboardShortcut, Margin, Modifiers,
     style::{Spacing, TextStyle},
     vec2,
 };
 use simple_logger::SimpleLogger;
 
 /// Size of the native application window
-const WINDOW_SIZE: egui::Vec2 = egui::vec2(450.0, 340.0);
+const WINDOW_SIZE: egui::Vec2 = egui::vec2(450.0, 480.0);
 
 /// Max number of frames per second
 const FPS_LIMIT: u32 = 25;
 
+/// Max number of entries in the recent files list
+const RECENT_FILES_LIMIT: usize = 10;
+
+/// Shortcut for opening a file
+const OPEN_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::O);
+
+/// Shortcut for reloading the current file
+const RELOAD_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::R);
+
+/// Shortcut for re-reading and sending the current file
+const RESEND_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::R);
+
+/// Shortcut for selecting the next output device
+const NEXT_DEVICE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::D);
+
+/// Shortcut for toggling auto-start
+const AUTO_START_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::A);
+
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Command-line arguments
+#[derive(Debug, Default, PartialEq)]
+struct Arguments {
+    /// Write events as JSON lines to stdout
+    json: bool,
+
+    /// Files passed by the file manager or on the command line
+    file_paths: Vec<std::path::PathBuf>,
+}
+
+impl Arguments {
+    /// Parse the arguments without the program name
+    ///
+    /// Only known flags are recognized, everything else is a file. After `--`,
+    /// all arguments are files, so names starting with `-` can be passed.
+    fn parse(args: impl IntoIterator<Item = std::ffi::OsString>) -> Self {
+        let mut arguments = Self::default();
+        let mut flags = true;
+
+        for arg in args {
+            if flags && arg == "--" {
+                flags = false;
+            } else if flags && arg == "--json" {
+                arguments.json = true;
+            } else {
+                arguments.file_paths.push(arg.into());
+            }
+        }
+
+        arguments
+    }
+}
+
 /// Starts the application
 fn main() {
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Info)
-        .with_module_level(env!("CARGO_CRATE_NAME"), log::LevelFilter::Debug)
-        .init()
-        .unwrap();
+    let Arguments { json, file_paths } = Arguments::parse(std::env::args_os().skip(1));
+
+    if json {
+        report::enable().unwrap();
+    } else {
+        SimpleLogger::new()
+            .with_level(log::LevelFilter::Info)
+            .with_module_level(env!("CARGO_CRATE_NAME"), log::LevelFilter::Debug)
+            .init()
+            .unwrap();
+    }
+
+    if instance::forward(&file_paths) {
+        return;
+    }
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -73,7 +154,7 @@ fn main() {
                     ..Default::default()
                 };
             });
-            Ok(Box::new(App::new(cc)))
+            Ok(Box::new(App::new(cc, file_paths)))
         }),
     )
     .ok();
@@ -101,9 +182,34 @@ pub struct App {
     #[serde(skip)]
     file_packet_count: usize,
 
-    /// Selected MIDI device
+    /// Non-fatal problems found in the file
+    #[serde(skip)]
+    file_warnings: Vec<Error>,
+
+    /// Packets read from the file
+    #[serde(skip)]
+    file_packets: Vec<Vec<u8>>,
+
+    /// Modification time of the file when loaded
+    #[serde(skip)]
+    file_modified: Option<std::time::SystemTime>,
+
+    /// SHA-256 hash of the file when loaded
+    #[serde(skip)]
+    file_hash: String,
+
+    /// Key of the selected MIDI device
     selected_device: Option<String>,
 
+    /// Key of the selected MIDI input for replies of the device
+    selected_input: Option<String>,
+
+    /// Inputs chosen manually for outputs, overriding the automatic pairing
+    input_overrides: std::collections::HashMap<String, String>,
+
+    /// Recently opened files, most recent first
+    recent_files: Vec<std::path::PathBuf>,
+
     /// Interval in ms between packets
     packet_interval: u64,
 
@@ -113,6 +219,65 @@ pub struct App {
     /// Always on top flag
     always_on_top: bool,
 
+    /// Reject files with invalid packet contents instead of warning only
+    strict_mode: bool,
+
+    /// Refuse to send files with checksum errors
+    block_checksum_errors: bool,
+
+    /// Verify transfers via dump request
+    verify_transfer: bool,
+
+    /// Dump request in hex notation, empty for automatic
+    dump_request: String,
+
+    /// Wait for a reply of the device after each packet
+    wait_for_reply: bool,
+
+    /// Expected reply in hex notation, empty for any SysEx message
+    reply_pattern: String,
+
+    /// Time in ms to wait for a reply
+    reply_timeout: u64,
+
+    /// Number of times a packet is sent again if no reply arrives
+    reply_retries: u32,
+
+    /// Raw MIDI devices are listed as outputs
+    raw_midi_outputs: bool,
+
+    /// Backend providing the MIDI ports
+    midi_backend: midi::Backend,
+
+    /// Addresses of network MIDI peers listed as devices
+    network_peers: Vec<String>,
+
+    /// Address of a network MIDI peer being entered
+    #[serde(skip)]
+    network_peer_input: String,
+
+    /// Remote control server enabled flag
+    remote_control: bool,
+
+    /// TCP port of the remote control server
+    remote_port: u16,
+
+    /// Running remote control server
+    #[serde(skip)]
+    remote_server: Option<remote::Server>,
+
+    /// Socket receiving files from further instances
+    #[serde(skip)]
+    instance_listener: Option<instance::Listener>,
+
+    /// Port scanning is paused during transfers
+    #[serde(skip)]
+    port_scan_paused: Arc<std::sync::atomic::AtomicBool>,
+
+    /// Rescan requested during a transfer, done when it has ended
+    #[serde(skip)]
+    rescan_pending: bool,
+
     /// Transfer state
     #[serde(skip)]
     transfer_state: TransferState,
@@ -121,6 +286,26 @@ pub struct App {
     #[serde(skip)]
     transfer_progress: f32,
 
+    /// Total number of bytes after each packet of the running transfer
+    #[serde(skip)]
+    transfer_packet_ends: Vec<usize>,
+
+    /// Number of bytes transferred
+    #[serde(skip)]
+    transfer_bytes_sent: usize,
+
+    /// Start time of the transfer
+    #[serde(skip)]
+    transfer_start: Option<std::time::Instant>,
+
+    /// Duration of the last completed transfer
+    #[serde(skip)]
+    transfer_duration: Option<Duration>,
+
+    /// Verification of the transfer is in progress
+    #[serde(skip)]
+    verifying: bool,
+
     /// MIDI handler
     #[serde(skip)]
     midi: Arc<Mutex<midi::MidiConnector>>,
@@ -150,6 +335,36 @@ pub struct App {
 
     /// Zoom factor.
     zoom_factor: f32,
+
+    /// Editor for Roland DT1 packets
+    #[serde(skip)]
+    roland_editor: editor::RolandEditor,
+
+    /// Window for merging files
+    #[serde(skip)]
+    merge_window: merge::MergeWindow,
+
+    /// Window with the verification results
+    #[serde(skip)]
+    verify_report: verify::VerifyReport,
+
+    /// Window with the incoming MIDI messages
+    #[serde(skip)]
+    monitor_window: monitor::MonitorWindow,
+
+    /// History of transfers
+    history: Vec<history::Entry>,
+
+    /// History entry of the running transfer
+    #[serde(skip)]
+    history_entry: Option<history::Entry>,
+
+    /// Window with the transfer history
+    #[serde(skip)]
+    history_window: history::HistoryWindow,
+
+    /// Watch folder for sending files automatically
+    folder_watch: watch::FolderWatch,
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -176,6 +391,22 @@ impl FileType {
 
         Ok(file_type)
     }
+
+    /// Return the file extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileType::SysEx => "syx",
+            FileType::SMF => "mid",
+        }
+    }
+
+    /// Return a description for file dialogs
+    pub fn description(&self) -> &'static str {
+        match self {
+            FileType::SysEx => "SysEx file",
+            FileType::SMF => "Standard MIDI file",
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -189,15 +420,57 @@ pub enum Message {
     /// Force rescanning of devices
     RescanDevices,
 
+    /// Ports were added or removed
+    PortsChanged,
+
+    /// Network MIDI session was started or failed
+    NetworkSessionResult(Result<(), String>),
+
+    /// Address entered for a network MIDI peer was resolved, `None` if not found
+    NetworkPeerResolved(String, Option<String>),
+
     /// Select a device by name
     SelectDevice(String),
 
+    /// Select an input by name
+    SelectInput(String),
+
+    /// Select an input by name as override for the selected device
+    OverrideInput(String),
+
     /// Start the transfer
     StartTransfer,
 
+    /// Start the transfer of packets other than the ones from the file
+    SendPackets(Vec<Vec<u8>>),
+
+    /// Start the transfer of the loaded file with a packet interval in ms, used for re-runs
+    RepeatTransfer(u64),
+
     /// Packet with number transferred
     PacketTransferred(usize),
 
+    /// Verification of the transfer started
+    VerificationStarted,
+
+    /// Verification finished with results per packet
+    VerificationFinished(Vec<verify::PacketResult>),
+
+    /// File in the watch folder was created or modified
+    WatchedFileChanged(std::path::PathBuf),
+
+    /// Load a file without starting the transfer
+    LoadFile(std::path::PathBuf),
+
+    /// Cancel the running transfer
+    CancelTransfer,
+
+    /// Files to open from the command line or forwarded by another instance
+    OpenFiles(Vec<std::path::PathBuf>),
+
+    /// Focus the window, requested by another instance started without files
+    FocusWindow,
+
     /// Transfer finished successfully
     TransferFinished,
 
@@ -211,7 +484,7 @@ pub enum Message {
 ////////////////////////////////////////////////////////////////////////////////
 
 /// Transfer states
-#[derive(PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub enum TransferState {
     /// Initial state
     Idle,
@@ -224,6 +497,25 @@ pub enum TransferState {
 
     /// Transfer was cancelled
     Cancelled,
+
+    /// Transfer failed with an error
+    Failed,
+}
+
+impl std::fmt::Display for TransferState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Idle => "Idle",
+                Self::Running => "Running",
+                Self::Finished => "Finished",
+                Self::Cancelled => "Cancelled",
+                Self::Failed => "Failed",
+            }
+        )
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -235,12 +527,42 @@ impl Default for App {
             file_type: None,
             file_size: 0,
             file_packet_count: 0,
+            file_warnings: Vec::new(),
+            file_packets: Vec::new(),
+            file_modified: None,
+            file_hash: String::new(),
             selected_device: None,
+            selected_input: None,
+            input_overrides: std::collections::HashMap::new(),
+            recent_files: Vec::new(),
             packet_interval: 20,
             auto_start: false,
             always_on_top: false,
+            strict_mode: false,
+            block_checksum_errors: false,
+            verify_transfer: false,
+            dump_request: String::new(),
+            wait_for_reply: false,
+            reply_pattern: String::new(),
+            reply_timeout: 1000,
+            reply_retries: 2,
+            raw_midi_outputs: false,
+            midi_backend: midi::Backend::default(),
+            network_peers: Vec::new(),
+            network_peer_input: String::new(),
+            remote_control: false,
+            remote_port: remote::DEFAULT_PORT,
+            remote_server: None,
+            instance_listener: None,
+            port_scan_paused: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            rescan_pending: false,
             transfer_state: TransferState::Idle,
             transfer_progress: 0.0,
+            transfer_packet_ends: Vec::new(),
+            transfer_bytes_sent: 0,
+            transfer_start: None,
+            transfer_duration: None,
+            verifying: false,
             midi: Arc::new(Mutex::new(midi::MidiConnector::new())),
             error_message: None,
             message_channel: std::sync::mpsc::channel(),
@@ -248,6 +570,14 @@ impl Default for App {
             frame_interval: std::time::Duration::from_secs_f64(1.0 / FPS_LIMIT as f64),
             frame_count: 0,
             zoom_factor: 1.0,
+            roland_editor: editor::RolandEditor::default(),
+            merge_window: merge::MergeWindow::default(),
+            verify_report: verify::VerifyReport::default(),
+            monitor_window: monitor::MonitorWindow::default(),
+            history: Vec::new(),
+            history_entry: None,
+            history_window: history::HistoryWindow::default(),
+            folder_watch: watch::FolderWatch::default(),
         }
     }
 }
@@ -264,6 +594,27 @@ impl eframe::App for App {
             self.process_message(&message, ctx);
         }
 
+        self.port_scan_paused.store(
+            self.transfer_state == TransferState::Running,
+            std::sync::atomic::Ordering::Relaxed,
+        );
+
+        if self.transfer_state != TransferState::Running && self.rescan_pending {
+            self.rescan_pending = false;
+            self.message_channel.0.send(Message::RescanDevices).ok();
+        }
+
+        if let Some(server) = &self.remote_server {
+            server.set_status(remote::Status {
+                state: self.transfer_state.clone(),
+                progress: self.transfer_progress,
+                file: self.file_path.clone(),
+                device: self.selected_device.clone(),
+                connected: self.midi.lock().unwrap().output_name().is_some(),
+                error: self.error_message.clone(),
+            });
+        }
+
         // Continuous run mode is required for message processing
         ctx.request_repaint_after(Duration::from_millis(1000 / FPS_LIMIT as u64));
     }
@@ -290,6 +641,316 @@ impl eframe::App for App {
             self.zoom_factor = zoom_factor;
         }
 
+        self.handle_shortcuts(ui.ctx());
+
+        // Top panel with menu
+        egui::Panel::top("menu_panel").show(ui, |ui| {
+            egui::MenuBar::new().ui(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    ui.add_enabled_ui(self.transfer_state != TransferState::Running, |ui| {
+                        if ui
+                            .add(
+                                egui::Button::new("Open…")
+                                    .shortcut_text(ui.ctx().format_shortcut(&OPEN_SHORTCUT)),
+                            )
+                            .clicked()
+                        {
+                            self.open_file_dialog();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.file_path.is_some(),
+                                egui::Button::new("Reload")
+                                    .shortcut_text(ui.ctx().format_shortcut(&RELOAD_SHORTCUT)),
+                            )
+                            .clicked()
+                        {
+                            self.reload_file();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.file_path.is_some(),
+                                egui::Button::new("Resend")
+                                    .shortcut_text(ui.ctx().format_shortcut(&RESEND_SHORTCUT)),
+                            )
+                            .on_hover_text("Read the file again if changed and send it")
+                            .clicked()
+                        {
+                            self.resend_file();
+                        }
+                        ui.menu_button("Open Recent", |ui| {
+                            let mut selected = None;
+                            for path in &self.recent_files {
+                                if ui
+                                    .button(
+                                        path.file_name()
+                                            .and_then(std::ffi::OsStr::to_str)
+                                            .unwrap_or("Invalid filename"),
+                                    )
+                                    .on_hover_text(path.to_str().unwrap_or("Invalid filename"))
+                                    .clicked()
+                                {
+                                    selected = Some(path.clone());
+                                }
+                            }
+                            if let Some(path) = selected {
+                                self.open_file(&path);
+                            }
+                            ui.separator();
+                            if ui
+                                .add_enabled(
+                                    !self.recent_files.is_empty(),
+                                    egui::Button::new("Clear Recent Files"),
+                                )
+                                .clicked()
+                            {
+                                self.recent_files.clear();
+                            }
+                        });
+                    });
+                });
+                ui.menu_button("Tools", |ui| {
+                    if ui
+                        .add_enabled(
+                            self.file_path.is_some(),
+                            egui::Button::new("Roland DT1 Editor…"),
+                        )
+                        .clicked()
+                        && let Some(path) = &self.file_path
+                    {
+                        self.roland_editor.open(path, &self.file_packets);
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(
+                            matches!(self.file_type, Some(FileType::SMF)),
+                            egui::Button::new("Export as SysEx File…"),
+                        )
+                        .on_hover_text("Save all SysEx packets as raw .syx file")
+                        .clicked()
+                    {
+                        self.export_file(FileType::SysEx);
+                    }
+                    if ui
+                        .add_enabled(
+                            matches!(self.file_type, Some(FileType::SysEx)),
+                            egui::Button::new("Export as MIDI File…"),
+                        )
+                        .on_hover_text(
+                            "Save all packets as Standard MIDI file,\nspaced by the packet delay",
+                        )
+                        .clicked()
+                    {
+                        self.export_file(FileType::SMF);
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(
+                            self.file_packet_count > 1
+                                && self.transfer_state != TransferState::Running,
+                            egui::Button::new("Split into Packets…"),
+                        )
+                        .on_hover_text("Save each packet as separate .syx file")
+                        .clicked()
+                    {
+                        self.split_file();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.transfer_state != TransferState::Running,
+                            egui::Button::new("Merge Files…"),
+                        )
+                        .on_hover_text("Combine several files into one .syx file")
+                        .clicked()
+                    {
+                        self.merge_window.open(Vec::new());
+                    }
+                    ui.separator();
+                    if ui.button("Transfer History…").clicked() {
+                        self.history_window.open = true;
+                    }
+                    if ui
+                        .button("Watch Folder…")
+                        .on_hover_text("Send new and modified files in a folder automatically")
+                        .clicked()
+                    {
+                        self.folder_watch.open = true;
+                    }
+                    if ui
+                        .button("MIDI Monitor…")
+                        .on_hover_text("Show the messages received on the input")
+                        .clicked()
+                    {
+                        self.monitor_window.open = true;
+                    }
+                });
+                ui.menu_button("Options", |ui| {
+                    ui.checkbox(&mut self.verify_transfer, "Verify after transfer")
+                        .on_hover_text(
+                            "Request a dump from the device after the transfer\n\
+                             and compare it with the sent packets",
+                        );
+                    ui.horizontal(|ui| {
+                        ui.label("Dump request:");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.dump_request)
+                                .hint_text("Automatic")
+                                .desired_width(160.0),
+                        )
+                        .on_hover_text(
+                            "SysEx message in hex notation,\n\
+                             leave empty for Roland and Yamaha defaults",
+                        );
+                    });
+                    ui.separator();
+                    let overridden = self
+                        .selected_device
+                        .as_ref()
+                        .is_some_and(|device| self.input_overrides.contains_key(device));
+                    if ui
+                        .add_enabled(overridden, egui::Button::new("Pair input automatically"))
+                        .on_hover_text(
+                            "Forget the manually chosen input for this device\n\
+                             and select the input with the most similar name",
+                        )
+                        .clicked()
+                        && let Some(device) = self.selected_device.clone()
+                    {
+                        self.input_overrides.remove(&device);
+                        self.pair_input(&device);
+                    }
+                    ui.separator();
+                    ui.checkbox(&mut self.wait_for_reply, "Wait for reply after each packet")
+                        .on_hover_text(
+                            "Send the next packet after the device acknowledged\n\
+                             the previous one on the selected input\n\
+                             instead of waiting for the packet delay",
+                        );
+                    ui.add_enabled_ui(self.wait_for_reply, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Reply pattern:");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.reply_pattern)
+                                    .hint_text("Any SysEx")
+                                    .desired_width(160.0),
+                            )
+                            .on_hover_text(
+                                "Start of the reply in hex notation,\n\
+                                 XX matches any byte",
+                            );
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Timeout:");
+                            ui.add(
+                                egui::DragValue::new(&mut self.reply_timeout)
+                                    .range(10..=10000)
+                                    .suffix(" ms"),
+                            );
+                            ui.label("Retries:");
+                            ui.add(egui::DragValue::new(&mut self.reply_retries).range(0..=10));
+                        });
+                    });
+                    if cfg!(feature = "jack") {
+                        ui.separator();
+                        // Changing the backend closes the connection used by a transfer
+                        let running = self.transfer_state == TransferState::Running;
+                        ui.add_enabled_ui(!running, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("MIDI backend:");
+                                let mut changed = false;
+                                for backend in [midi::Backend::Native, midi::Backend::Jack] {
+                                    changed |= ui
+                                        .radio_value(
+                                            &mut self.midi_backend,
+                                            backend,
+                                            backend.to_string(),
+                                        )
+                                        .changed();
+                                }
+                                if changed {
+                                    self.midi.lock().unwrap().set_backend(self.midi_backend);
+                                    self.message_channel.0.send(Message::RescanDevices).ok();
+                                }
+                            });
+                        });
+                    }
+                    if cfg!(target_os = "linux") {
+                        ui.separator();
+                        let running = self.transfer_state == TransferState::Running;
+                        if ui
+                            .add_enabled(
+                                !running,
+                                egui::Checkbox::new(
+                                    &mut self.raw_midi_outputs,
+                                    "Raw MIDI devices as outputs",
+                                ),
+                            )
+                            .on_hover_text(
+                                "List ALSA raw MIDI devices (hw:) as additional outputs,\n\
+                                 bypassing the sequencer",
+                            )
+                            .changed()
+                        {
+                            self.midi
+                                .lock()
+                                .unwrap()
+                                .set_raw_outputs(self.raw_midi_outputs);
+                            self.message_channel.0.send(Message::RescanDevices).ok();
+                        }
+                    }
+                    ui.separator();
+                    self.network_peers_ui(ui);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let mut changed = ui
+                            .checkbox(&mut self.remote_control, "Remote control on port")
+                            .on_hover_text("Accept commands via HTTP from this computer")
+                            .changed();
+                        let port =
+                            ui.add(egui::DragValue::new(&mut self.remote_port).range(1024..=65535));
+                        changed |= port.drag_stopped() || port.lost_focus();
+                        if changed {
+                            self.update_remote_server();
+                        }
+                    });
+                });
+            });
+        });
+
+        if let Some(editor::Action::Send(packets)) = self
+            .roland_editor
+            .show(ui.ctx(), self.transfer_state == TransferState::Running)
+        {
+            self.message_channel
+                .0
+                .send(Message::SendPackets(packets))
+                .ok();
+        }
+
+        self.verify_report.show(ui.ctx());
+        self.folder_watch.show(ui.ctx(), &self.message_channel.0);
+        self.monitor_window.show(
+            ui.ctx(),
+            &self.midi,
+            self.selected_input.clone(),
+            self.transfer_state == TransferState::Running,
+            &self.message_channel.0,
+        );
+
+        if let Some(history::Action::Rerun(entry)) = self.history_window.show(
+            ui.ctx(),
+            &mut self.history,
+            self.transfer_state == TransferState::Running,
+        ) {
+            self.rerun_transfer(entry);
+        }
+
+        if let Some(merge::Action::Load(path)) = self.merge_window.show(ui.ctx())
+            && self.transfer_state != TransferState::Running
+        {
+            self.load_file(&path);
+        }
+
         // Bottom panel with app version
         egui::Panel::bottom("bottom_panel").show(ui, |ui| {
             ui.set_height(25.0);
@@ -311,18 +972,36 @@ impl eframe::App for App {
                     ui.disable();
                 }
 
+                let (output_connected, input_connected) = {
+                    let midi = self.midi.lock().unwrap();
+                    (midi.output_name().is_some(), midi.input_name().is_some())
+                };
+
                 device_selection(
                     ui,
+                    "Device:",
                     self.midi.lock().unwrap().get_outputs(),
                     self.selected_device.to_owned(),
+                    output_connected,
                     &self.message_channel.0,
+                    Message::SelectDevice,
                 );
 
-                ui.add_space(20.0);
+                device_selection(
+                    ui,
+                    "Input:",
+                    self.midi.lock().unwrap().get_inputs(),
+                    self.selected_input.to_owned(),
+                    input_connected,
+                    &self.message_channel.0,
+                    Message::OverrideInput,
+                );
+
+                ui.add_space(14.0);
 
                 ui.group(|ui| {
                     ui.set_width(ui.available_width());
-                    ui.set_height(70.0);
+                    ui.set_height(90.0);
 
                     ui.centered_and_justified(|ui| {
                         if !ui.ctx().input(|i| i.raw.hovered_files.is_empty())
@@ -352,8 +1031,33 @@ impl eframe::App for App {
                                     .on_hover_text("File size in bytes");
                                 ui.end_row();
                                 ui.label("Packets:");
-                                ui.label(format!("{}", self.file_packet_count))
+                                let checksum_errors = self.checksum_error_count();
+                                if checksum_errors > 0 {
+                                    ui.label(
+                                        egui::RichText::new(format!(
+                                            "{} ({} bad checksums)",
+                                            self.file_packet_count, checksum_errors
+                                        ))
+                                        .color(egui::Color32::ORANGE),
+                                    )
                                     .on_hover_text("Total number of packets in file");
+                                } else {
+                                    ui.label(format!("{}", self.file_packet_count))
+                                        .on_hover_text("Total number of packets in file");
+                                }
+                                ui.end_row();
+                                ui.label("Duration:");
+                                ui.label(format!(
+                                    "~{}",
+                                    format_duration(estimate_duration(
+                                        &self.file_packets,
+                                        Duration::from_millis(self.packet_interval)
+                                    ))
+                                ))
+                                .on_hover_text(
+                                    "Estimated transfer duration based on the\n\
+                                     packet delay and the standard MIDI data rate",
+                                );
                                 ui.end_row();
                             });
                         } else {
@@ -366,28 +1070,19 @@ impl eframe::App for App {
                         && self.transfer_state != TransferState::Running
                     {
                         let dropped_files = ui.ctx().input(|i| i.raw.dropped_files.clone());
-                        if let Some(file) = dropped_files.into_iter().next() {
-                            self.transfer_progress = 0.0;
-                            self.transfer_state = TransferState::Idle;
-                            match self.process_file(file.path()) {
-                                Ok(()) => {
-                                    self.error_message = None;
-                                    if self.auto_start {
-                                        self.message_channel.0.send(Message::StartTransfer).ok();
-                                    }
-                                }
-                                Err(error) => {
-                                    self.error_message = Some(error.to_string());
-                                }
-                            }
-                        }
+                        self.open_files(
+                            dropped_files
+                                .into_iter()
+                                .map(|file| file.path().to_path_buf())
+                                .collect(),
+                        );
                     }
                 });
 
                 ui.add_space(10.0);
 
                 egui::Grid::new("settings").num_columns(2).show(ui, |ui| {
-                    ui.set_height(40.0);
+                    ui.set_height(80.0);
                     ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
                         ui.set_height(ui.available_height());
                         ui.label("Delay between packets:");
@@ -422,6 +1117,10 @@ impl eframe::App for App {
                                     ));
                                 self.always_on_top = always_on_top;
                             }
+                            ui.checkbox(&mut self.strict_mode, "Strict mode")
+                                .on_hover_text("Reject files with invalid packet contents");
+                            ui.checkbox(&mut self.block_checksum_errors, "Block bad checksums")
+                                .on_hover_text("Refuse to send files with checksum errors");
                         });
                     });
                     ui.end_row();
@@ -459,11 +1158,7 @@ impl eframe::App for App {
                             .on_hover_text("Cancel file transfer")
                             .clicked()
                         {
-                            self.transmit_thread_sender
-                                .as_ref()
-                                .unwrap()
-                                .send(true)
-                                .ok();
+                            self.cancel_transfer();
                         };
                     });
                 });
@@ -488,13 +1183,40 @@ impl eframe::App for App {
                             ));
                         }
                         TransferState::Running => {
-                            ui.add(egui::Label::new("Transfer in progress."));
+                            if self.verifying {
+                                ui.add(egui::Label::new("Verifying transfer."));
+                            } else {
+                                ui.add(egui::Label::new("Transfer in progress."));
+                                ui.add(egui::Label::new(self.transfer_statistics()));
+                            }
                         }
                         TransferState::Finished => {
+                            let text = match self.transfer_duration {
+                                Some(duration) => {
+                                    format!("Transfer finished in {}.", format_duration(duration))
+                                }
+                                None => "Transfer finished.".to_string(),
+                            };
                             ui.add(egui::Label::new(
-                                egui::RichText::new("Transfer finished.")
-                                    .color(egui::Color32::GREEN),
+                                egui::RichText::new(text).color(egui::Color32::GREEN),
                             ));
+                            if let Some(summary) = self.verify_report.summary() {
+                                let color = if self.verify_report.all_match() {
+                                    egui::Color32::GREEN
+                                } else {
+                                    egui::Color32::ORANGE
+                                };
+                                if ui
+                                    .add(
+                                        egui::Label::new(egui::RichText::new(summary).color(color))
+                                            .sense(egui::Sense::click()),
+                                    )
+                                    .on_hover_text("Click to show the verification report")
+                                    .clicked()
+                                {
+                                    self.verify_report.open = true;
+                                }
+                            }
                         }
                         TransferState::Cancelled => {
                             ui.add(egui::Label::new(
@@ -502,6 +1224,33 @@ impl eframe::App for App {
                                     .color(egui::Color32::RED),
                             ));
                         }
+                        TransferState::Failed => {
+                            ui.add(egui::Label::new(
+                                egui::RichText::new("Transfer failed.").color(egui::Color32::RED),
+                            ));
+                        }
+                    }
+
+                    if let Some(warning) = self.file_warnings.first() {
+                        let text = if self.file_warnings.len() > 1 {
+                            format!(
+                                "Warning: {} (+{} more)",
+                                warning,
+                                self.file_warnings.len() - 1
+                            )
+                        } else {
+                            format!("Warning: {}", warning)
+                        };
+                        ui.add(egui::Label::new(
+                            egui::RichText::new(text).color(egui::Color32::ORANGE),
+                        ))
+                        .on_hover_text(
+                            self.file_warnings
+                                .iter()
+                                .map(|warning| warning.to_string())
+                                .collect::<Vec<_>>()
+                                .join("\n"),
+                        );
                     }
                 }
             });
@@ -512,9 +1261,9 @@ impl eframe::App for App {
 }
 
 impl App {
-    /// Create the application
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let app = if let Some(storage) = cc.storage {
+    /// Create the application, opening the files if any
+    pub fn new(cc: &eframe::CreationContext<'_>, file_paths: Vec<std::path::PathBuf>) -> Self {
+        let mut app: Self = if let Some(storage) = cc.storage {
             eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
         } else {
             Self::default()
@@ -524,13 +1273,39 @@ impl App {
 
         app.message_channel.0.send(Message::Init).ok();
 
+        if !file_paths.is_empty() {
+            // Scan first, so that the device is selected before an auto-started transfer
+            app.message_channel.0.send(Message::RescanDevices).ok();
+            app.message_channel
+                .0
+                .send(Message::OpenFiles(file_paths))
+                .ok();
+        }
+
         let message_sender = app.message_channel.0.clone();
-        std::thread::spawn(move || {
-            loop {
-                message_sender.send(Message::RescanDevices).ok();
-                std::thread::sleep(std::time::Duration::from_millis(250));
+        app.folder_watch.start(&message_sender);
+
+        match instance::listen(app.message_channel.0.clone()) {
+            Ok(listener) => app.instance_listener = Some(listener),
+            Err(error) => log::error!("Single-instance mode not available: {}", error),
+        }
+
+        app.update_remote_server();
+
+        {
+            let mut midi = app.midi.lock().unwrap();
+            midi.set_raw_outputs(app.raw_midi_outputs);
+            midi.set_network_peers(app.network_peers.clone());
+            if cfg!(feature = "jack") {
+                midi.set_backend(app.midi_backend);
             }
-        });
+        }
+
+        hotplug::start(
+            app.midi.clone(),
+            app.message_channel.0.clone(),
+            app.port_scan_paused.clone(),
+        );
 
         app
     }
@@ -541,71 +1316,873 @@ impl App {
             Message::Init => {
                 ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(WINDOW_SIZE));
             }
+            Message::RescanDevices if self.transfer_state == TransferState::Running => {
+                // Scanning could close the connection used by the transfer
+                self.rescan_pending = true;
+            }
             Message::RescanDevices => {
-                let mut midi = self.midi.lock().unwrap();
-                let ports_changed = midi.scan_ports();
-                if ports_changed && let Some(device) = &self.selected_device {
-                    self.message_channel
-                        .0
-                        .send(Message::SelectDevice(device.to_owned()))
-                        .ok();
+                self.midi.lock().unwrap().scan_ports();
+                self.reconnect_ports();
+            }
+            Message::PortsChanged => {
+                self.reconnect_ports();
+            }
+            Message::NetworkSessionResult(result) => match result {
+                Ok(()) => self.error_message = None,
+                Err(error) => self.error_message = Some(error.to_owned()),
+            },
+            Message::NetworkPeerResolved(text, address) => match address {
+                Some(address) => {
+                    if !self.network_peers.contains(address) {
+                        self.network_peers.push(address.to_owned());
+                        self.midi
+                            .lock()
+                            .unwrap()
+                            .set_network_peers(self.network_peers.clone());
+                        self.message_channel.0.send(Message::RescanDevices).ok();
+                    }
+                    if self.network_peer_input.trim() == text {
+                        self.network_peer_input.clear();
+                    }
                 }
+                None => {
+                    self.error_message = Some(format!("Network MIDI address {} not found.", text));
+                }
+            },
+            Message::SelectDevice(name) if self.transfer_state == TransferState::Running => {
+                log::warn!("Transfer running, device {} not selected.", name);
             }
             Message::SelectDevice(name) => {
                 log::debug!("Device {} selected.", name);
-                match self.midi.lock().unwrap().select_output(name.to_string()) {
-                    Ok(()) => self.error_message = None,
-                    Err(err) => self.error_message = Some(err.to_string()),
+                let (key, connecting) = {
+                    let mut midi = self.midi.lock().unwrap();
+                    match midi.select_output(name) {
+                        Ok(()) => self.error_message = None,
+                        Err(err) => self.error_message = Some(err.to_string()),
+                    }
+                    let connecting = midi.connecting_output().cloned();
+                    // Names stored by earlier versions are replaced by the key
+                    let key = midi
+                        .output_port()
+                        .or(connecting.as_ref())
+                        .map_or_else(|| name.to_owned(), |port| port.key.clone());
+                    (key, connecting)
+                };
+                if let Some(port) = connecting {
+                    self.start_network_session(port);
                 }
 
-                self.selected_device = Some(name.to_owned())
+                self.migrate_input_overrides();
+                self.pair_input(&key);
+                self.selected_device = Some(key);
             }
-            Message::StartTransfer => {
-                self.transfer_state = TransferState::Running;
-                let file_path = self.file_path.as_ref().unwrap().clone();
-                let midi = self.midi.clone();
-                let packet_interval = self.packet_interval;
-                let message_sender = self.message_channel.0.clone();
-                let message_sender_result = self.message_channel.0.clone();
-                let (sender, receiver): (
-                    std::sync::mpsc::Sender<bool>,
-                    std::sync::mpsc::Receiver<bool>,
-                ) = std::sync::mpsc::channel();
-                self.transmit_thread_sender = Some(sender);
-                std::thread::spawn(move || {
-                    let result = send_sysex(
-                        file_path,
-                        midi,
-                        std::time::Duration::from_millis(packet_interval),
-                        message_sender,
-                        receiver,
-                    );
-                    match result {
-                        Ok(finished) => {
-                            if finished {
-                                message_sender_result.send(Message::TransferFinished).ok();
-                            } else {
-                                message_sender_result.send(Message::TransferCancelled).ok();
-                            }
-                        }
-                        Err(error) => {
-                            message_sender_result
-                                .send(Message::Error(format!("{}", error)))
-                                .ok();
-                        }
+            Message::SelectInput(name) => {
+                log::debug!("Input {} selected.", name);
+                let key = {
+                    let mut midi = self.midi.lock().unwrap();
+                    match midi.select_input(name) {
+                        Ok(()) => self.error_message = None,
+                        Err(err) => self.error_message = Some(err.to_string()),
                     }
-                });
+                    midi.input_port()
+                        .map_or_else(|| name.to_owned(), |port| port.key.clone())
+                };
+
+                self.selected_input = Some(key)
             }
-            Message::PacketTransferred(packet_count) => {
-                self.transfer_progress = (*packet_count as f32) / (self.file_packet_count as f32)
+            Message::OverrideInput(name) => {
+                if let Some(device) = &self.selected_device {
+                    self.input_overrides.insert(device.clone(), name.clone());
+                }
+                self.message_channel
+                    .0
+                    .send(Message::SelectInput(name.to_owned()))
+                    .ok();
             }
-            Message::TransferFinished => {
+            Message::StartTransfer => {
+                self.start_transfer(self.file_packets.clone());
+            }
+            Message::SendPackets(packets) => {
+                self.start_transfer(packets.clone());
+            }
+            Message::RepeatTransfer(packet_interval) => {
+                self.start_transfer_with_interval(self.file_packets.clone(), *packet_interval);
+            }
+            Message::PacketTransferred(packet_count) => {
+                report::emit(report::Event::PacketTransferred {
+                    count: *packet_count,
+                });
+                let bytes_total = self
+                    .transfer_packet_ends
+                    .last()
+                    .copied()
+                    .unwrap_or_default();
+                self.transfer_bytes_sent = self
+                    .transfer_packet_ends
+                    .get(packet_count.saturating_sub(1))
+                    .copied()
+                    .unwrap_or(bytes_total);
+                self.transfer_progress = if bytes_total > 0 {
+                    self.transfer_bytes_sent as f32 / bytes_total as f32
+                } else {
+                    0.0
+                };
+            }
+            Message::VerificationStarted => {
+                self.verifying = true;
+            }
+            Message::VerificationFinished(results) => {
+                report::emit(report::Event::VerificationFinished {
+                    all_match: results
+                        .iter()
+                        .all(|result| *result == verify::PacketResult::Match),
+                    results: results.iter().map(ToString::to_string).collect(),
+                });
+                self.verifying = false;
+                self.verify_report.set_results(results.clone());
+                if !self.verify_report.all_match() {
+                    self.verify_report.open = true;
+                }
+            }
+            Message::TransferFinished => {
+                report::emit(report::Event::TransferFinished);
                 self.transfer_state = TransferState::Finished;
+                self.transfer_duration = self.transfer_start.map(|start| start.elapsed());
+                self.finish_history_entry(TransferState::Finished, None);
+                self.verifying = false;
+                self.send_next_watched_file();
             }
             Message::TransferCancelled => {
+                report::emit(report::Event::TransferCancelled);
                 self.transfer_state = TransferState::Cancelled;
+                self.verifying = false;
+                self.finish_history_entry(TransferState::Cancelled, None);
+                self.send_next_watched_file();
+            }
+            Message::Error(error) => {
+                report::emit(report::Event::Error {
+                    message: error.to_string(),
+                });
+                self.error_message = Some(error.to_string());
+                // Errors during verification don't affect the transfer itself
+                if self.transfer_state == TransferState::Running && !self.verifying {
+                    self.transfer_state = TransferState::Failed;
+                    self.finish_history_entry(TransferState::Failed, Some(error.to_string()));
+                    self.send_next_watched_file();
+                }
+            }
+            Message::LoadFile(path) => {
+                if self.transfer_state != TransferState::Running {
+                    self.load_file(path);
+                }
+            }
+            Message::CancelTransfer => {
+                self.cancel_transfer();
+            }
+            Message::FocusWindow => {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+            }
+            Message::OpenFiles(paths) => {
+                ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+                if self.transfer_state == TransferState::Running {
+                    log::warn!("Transfer running, files not opened.");
+                } else {
+                    self.open_files(paths.clone());
+                }
+            }
+            Message::WatchedFileChanged(path) => {
+                if self.transfer_state == TransferState::Running {
+                    self.folder_watch.enqueue(path);
+                } else {
+                    self.send_watched_file(path);
+                }
+            }
+        }
+    }
+
+    /// Connect the selected ports again if disconnected
+    fn reconnect_ports(&self) {
+        // Ports are not changed during a transfer
+        if self.transfer_state == TransferState::Running {
+            return;
+        }
+
+        let midi = self.midi.lock().unwrap();
+        if let Some(device) = &self.selected_device
+            && midi.output_port().map(|port| &port.key) != Some(device)
+            && midi.connecting_output().map(|port| &port.key) != Some(device)
+        {
+            self.message_channel
+                .0
+                .send(Message::SelectDevice(device.to_owned()))
+                .ok();
+        }
+        if let Some(input) = &self.selected_input
+            && midi.input_port().map(|port| &port.key) != Some(input)
+        {
+            self.message_channel
+                .0
+                .send(Message::SelectInput(input.to_owned()))
+                .ok();
+        }
+    }
+
+    /// List of network MIDI peers in the options menu
+    fn network_peers_ui(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+
+        ui.label("Network MIDI peers (RTP-MIDI):");
+        let mut removed = None;
+        for (index, peer) in self.network_peers.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.small_button("✖").on_hover_text("Remove peer").clicked() {
+                    removed = Some(index);
+                }
+                ui.label(peer);
+            });
+        }
+        if let Some(index) = removed {
+            self.network_peers.remove(index);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.network_peer_input)
+                    .hint_text("host:5004")
+                    .desired_width(160.0),
+            )
+            .on_hover_text("Address and control port of the peer");
+            if ui
+                .add_enabled(
+                    !self.network_peer_input.trim().is_empty(),
+                    egui::Button::new("Add"),
+                )
+                .clicked()
+            {
+                // Resolving a host name may take a while
+                let text = self.network_peer_input.trim().to_owned();
+                let message_sender = self.message_channel.0.clone();
+                std::thread::spawn(move || {
+                    let address = rtpmidi::parse_address(&text);
+                    message_sender
+                        .send(Message::NetworkPeerResolved(text, address))
+                        .ok();
+                });
+            }
+        });
+
+        if changed {
+            self.midi
+                .lock()
+                .unwrap()
+                .set_network_peers(self.network_peers.clone());
+            self.message_channel.0.send(Message::RescanDevices).ok();
+        }
+    }
+
+    /// Start a network MIDI session in a separate thread
+    ///
+    /// The invitation can take seconds if the peer doesn't answer, so the UI
+    /// and the connector must not wait for it.
+    fn start_network_session(&self, port: midi::Port) {
+        let midi = self.midi.clone();
+        let message_sender = self.message_channel.0.clone();
+
+        std::thread::spawn(move || {
+            let session = rtpmidi::Session::connect(rtpmidi::address(&port.id));
+            let result = midi.lock().unwrap().session_started(&port, session);
+            message_sender
+                .send(Message::NetworkSessionResult(
+                    result.map_err(|error| error.to_string()),
+                ))
+                .ok();
+        });
+    }
+
+    /// Replace port names stored by earlier versions in the input overrides by the keys
+    ///
+    /// Entries of ports not present are kept until they are found by a later scan.
+    fn migrate_input_overrides(&mut self) {
+        let midi = self.midi.lock().unwrap();
+        self.input_overrides = self
+            .input_overrides
+            .drain()
+            .map(|(device, input)| {
+                (
+                    midi.find_output(&device)
+                        .map_or(device, |port| port.key.clone()),
+                    midi.find_input(&input)
+                        .map_or(input, |port| port.key.clone()),
+                )
+            })
+            .collect();
+    }
+
+    /// Select the input paired with a device
+    ///
+    /// A manually chosen input takes precedence over the automatic pairing by name.
+    fn pair_input(&mut self, device: &str) {
+        let input = self
+            .input_overrides
+            .get(device)
+            .cloned()
+            .or_else(|| self.midi.lock().unwrap().paired_input(device));
+
+        if let Some(input) = input
+            && self.selected_input.as_ref() != Some(&input)
+        {
+            log::debug!("Input {} paired with device {}.", input, device);
+            self.message_channel
+                .0
+                .send(Message::SelectInput(input))
+                .ok();
+        }
+    }
+
+    /// Request the transmit thread to cancel the transfer
+    fn cancel_transfer(&self) {
+        if self.transfer_state == TransferState::Running
+            && let Some(sender) = &self.transmit_thread_sender
+        {
+            sender.send(true).ok();
+        }
+    }
+
+    /// Start or stop the remote control server according to the settings
+    fn update_remote_server(&mut self) {
+        let running_port = self.remote_server.as_ref().map(remote::Server::port);
+        if running_port == self.remote_control.then_some(self.remote_port) {
+            return;
+        }
+
+        // Release the port before starting again
+        self.remote_server = None;
+
+        if !self.remote_control {
+            return;
+        }
+
+        match remote::Server::start(
+            self.remote_port,
+            self.midi.clone(),
+            self.message_channel.0.clone(),
+        ) {
+            Ok(server) => self.remote_server = Some(server),
+            Err(error) => {
+                self.remote_control = false;
+                self.error_message = Some(format!("Remote control not available: {}", error));
+            }
+        }
+    }
+
+    /// Validate and send a file from the watch folder
+    ///
+    /// Files that can't be sent are logged and skipped in favour of the next queued one.
+    fn send_watched_file(&mut self, path: &std::path::Path) {
+        let mut next = Some(path.to_path_buf());
+
+        while let Some(path) = next {
+            if !self.load_file(&path) {
+                let error = self.error_message.clone().unwrap_or_default();
+                self.folder_watch
+                    .log(format!("Rejected {}: {}", path.display(), error));
+            } else if self.start_transfer(self.file_packets.clone()) {
+                self.folder_watch.transfer_started(&path);
+                return;
+            } else {
+                let error = self.error_message.clone().unwrap_or_default();
+                self.folder_watch.log(format!(
+                    "{} {}: {}",
+                    TransferState::Failed,
+                    path.display(),
+                    error
+                ));
+            }
+            next = self.folder_watch.next_queued();
+        }
+    }
+
+    /// Log the end of a transfer and send the next queued file from the watch folder
+    fn send_next_watched_file(&mut self) {
+        if let Some(path) = self.folder_watch.transfer_ended(&self.transfer_state) {
+            self.send_watched_file(&path);
+        }
+    }
+
+    /// Complete the history entry of the running transfer and add it to the history
+    fn finish_history_entry(&mut self, outcome: TransferState, error: Option<String>) {
+        if let Some(mut entry) = self.history_entry.take() {
+            entry.outcome = outcome;
+            entry.error = error;
+            self.history.push(entry);
+            if self.history.len() > history::HISTORY_LIMIT {
+                self.history.remove(0);
+            }
+        }
+    }
+
+    /// Repeat a transfer from the history if the file is unchanged
+    fn rerun_transfer(&mut self, entry: history::Entry) {
+        if self.transfer_state == TransferState::Running || entry.edited {
+            return;
+        }
+
+        match history::file_hash(&entry.file_path) {
+            Ok(hash) if hash == entry.file_hash => {}
+            Ok(_) => {
+                self.error_message = Some(Error::FileChanged.to_string());
+                return;
+            }
+            Err(error) => {
+                self.error_message = Some(error.to_string());
+                return;
+            }
+        }
+
+        // Entries of earlier versions only contain the name
+        let key = if entry.port_key.is_empty() {
+            &entry.port_name
+        } else {
+            &entry.port_key
+        };
+        let (port, selected_port) = {
+            let midi = self.midi.lock().unwrap();
+            (
+                midi.find_output(key).cloned(),
+                midi.output_port().map(|port| port.key.clone()),
+            )
+        };
+        let Some(port) = port else {
+            self.error_message = Some(
+                Error::DeviceNotAvailable {
+                    name: entry.port_name.clone(),
+                }
+                .to_string(),
+            );
+            return;
+        };
+        if selected_port.as_ref() != Some(&port.key) {
+            self.message_channel
+                .0
+                .send(Message::SelectDevice(port.key))
+                .ok();
+        }
+        if self.load_file(&entry.file_path) {
+            if !entry.packets_hash.is_empty()
+                && history::packets_hash(&self.file_packets) != entry.packets_hash
+            {
+                self.error_message = Some(Error::FileChanged.to_string());
+                return;
+            }
+            // The interval of the entry doesn't change the setting
+            self.message_channel
+                .0
+                .send(Message::RepeatTransfer(entry.packet_interval))
+                .ok();
+        }
+    }
+
+    /// Start the transfer of packets in a separate thread, returns if it was started
+    fn start_transfer(&mut self, packets: Vec<Vec<u8>>) -> bool {
+        self.start_transfer_with_interval(packets, self.packet_interval)
+    }
+
+    /// Show and report an error for a transfer that is not started, returns `false`
+    fn refuse_transfer(&mut self, message: String) -> bool {
+        report::emit(report::Event::Error {
+            message: message.clone(),
+        });
+        self.error_message = Some(message);
+        false
+    }
+
+    /// Start the transfer of packets with an interval in ms, returns if it was started
+    fn start_transfer_with_interval(
+        &mut self,
+        packets: Vec<Vec<u8>>,
+        packet_interval: u64,
+    ) -> bool {
+        if self.transfer_state == TransferState::Running {
+            return false;
+        }
+        if packets.is_empty() || self.file_path.is_none() {
+            return self.refuse_transfer(Error::NoFile.to_string());
+        }
+        if self.block_checksum_errors
+            && packets
+                .iter()
+                .any(|packet| matches!(checksum::verify(packet), checksum::Status::Invalid { .. }))
+        {
+            return self.refuse_transfer(Error::ChecksumBlocked.to_string());
+        }
+        let requests = if self.verify_transfer {
+            match self.verification_requests(&packets) {
+                Ok(requests) => Some(requests),
+                Err(error) => return self.refuse_transfer(error.to_string()),
+            }
+        } else {
+            None
+        };
+        let pacing = if self.wait_for_reply {
+            match self.reply_pacing() {
+                Ok(pacing) => Some(pacing),
+                Err(error) => return self.refuse_transfer(error.to_string()),
+            }
+        } else {
+            None
+        };
+        self.verify_report.clear();
+        self.transfer_state = TransferState::Running;
+        self.transfer_progress = 0.0;
+        self.transfer_packet_ends = packets
+            .iter()
+            .scan(0, |bytes, packet| {
+                *bytes += packet.len();
+                Some(*bytes)
+            })
+            .collect();
+        self.transfer_bytes_sent = 0;
+        self.transfer_start = Some(std::time::Instant::now());
+        let output_port = self.midi.lock().unwrap().output_port().cloned();
+        self.history_entry = self.file_path.as_ref().map(|path| history::Entry {
+            timestamp: chrono::Local::now(),
+            file_path: path.clone(),
+            file_hash: history::file_hash(path).unwrap_or_else(|error| {
+                log::error!("Hashing file failed: {}", error);
+                String::new()
+            }),
+            packets_hash: history::packets_hash(&packets),
+            edited: packets != self.file_packets,
+            port_name: output_port
+                .as_ref()
+                .map(|port| port.name.clone())
+                .unwrap_or_default(),
+            port_key: output_port.map(|port| port.key).unwrap_or_default(),
+            packet_count: packets.len(),
+            packet_interval,
+            outcome: TransferState::Running,
+            error: None,
+        });
+        self.transfer_duration = None;
+        let midi = self.midi.clone();
+        let message_sender = self.message_channel.0.clone();
+        let message_sender_result = self.message_channel.0.clone();
+        let (sender, receiver): (
+            std::sync::mpsc::Sender<bool>,
+            std::sync::mpsc::Receiver<bool>,
+        ) = std::sync::mpsc::channel();
+        self.transmit_thread_sender = Some(sender);
+        std::thread::spawn(move || {
+            let packet_interval = std::time::Duration::from_millis(packet_interval);
+            let result = send_sysex(
+                &packets,
+                midi.clone(),
+                packet_interval,
+                pacing.as_ref(),
+                message_sender,
+                &receiver,
+            );
+            match result {
+                Ok(finished) => {
+                    if finished {
+                        if let Some(requests) = requests {
+                            message_sender_result
+                                .send(Message::VerificationStarted)
+                                .ok();
+                            match verify::capture_replies(
+                                &midi,
+                                &requests,
+                                packet_interval,
+                                &receiver,
+                            ) {
+                                Ok(Some(replies)) => {
+                                    message_sender_result
+                                        .send(Message::VerificationFinished(verify::compare(
+                                            &packets, &replies,
+                                        )))
+                                        .ok();
+                                }
+                                Ok(None) => {
+                                    message_sender_result.send(Message::TransferCancelled).ok();
+                                    return;
+                                }
+                                Err(error) => {
+                                    message_sender_result
+                                        .send(Message::Error(format!("{}", error)))
+                                        .ok();
+                                }
+                            }
+                        }
+                        message_sender_result.send(Message::TransferFinished).ok();
+                    } else {
+                        message_sender_result.send(Message::TransferCancelled).ok();
+                    }
+                }
+                Err(error) => {
+                    message_sender_result
+                        .send(Message::Error(format!("{}", error)))
+                        .ok();
+                }
+            }
+        });
+
+        true
+    }
+
+    /// Return elapsed time, estimated remaining time and data rate of the transfer
+    fn transfer_statistics(&self) -> String {
+        let elapsed = self
+            .transfer_start
+            .map(|start| start.elapsed())
+            .unwrap_or_default();
+        let bytes_total = self
+            .transfer_packet_ends
+            .last()
+            .copied()
+            .unwrap_or_default();
+        let rate = if elapsed.as_secs_f64() > 0.0 {
+            self.transfer_bytes_sent as f64 / elapsed.as_secs_f64()
+        } else {
+            0.0
+        };
+
+        let remaining = if rate > 0.0 {
+            format_duration(Duration::from_secs_f64(
+                (bytes_total - self.transfer_bytes_sent) as f64 / rate,
+            ))
+        } else {
+            "-".to_string()
+        };
+
+        format!(
+            "Elapsed {}, remaining {}, {:.0} bytes/s",
+            format_duration(elapsed),
+            remaining,
+            rate
+        )
+    }
+
+    /// Return the settings for waiting on replies of the device
+    fn reply_pacing(&self) -> Result<pacing::ReplyPacing> {
+        if self.midi.lock().unwrap().input_name().is_none() {
+            return Err(anyhow!(Error::NoInput));
+        }
+
+        let pattern =
+            pacing::parse_pattern(&self.reply_pattern).ok_or(Error::InvalidReplyPattern)?;
+
+        Ok(pacing::ReplyPacing {
+            pattern,
+            timeout: Duration::from_millis(self.reply_timeout),
+            retries: self.reply_retries,
+        })
+    }
+
+    /// Return the dump requests for verifying a transfer of the packets
+    fn verification_requests(&self, packets: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+        if self.midi.lock().unwrap().input_name().is_none() {
+            return Err(anyhow!(Error::NoInput));
+        }
+
+        let configured = if self.dump_request.trim().is_empty() {
+            None
+        } else {
+            match midi::parse_hex(&self.dump_request) {
+                Some(request)
+                    if request.first() == Some(&midi::SYSEX_START_BYTE)
+                        && request.last() == Some(&midi::SYSEX_END_BYTE) =>
+                {
+                    Some(request)
+                }
+                _ => return Err(anyhow!(Error::InvalidDumpRequest)),
+            }
+        };
+
+        let requests = verify::dump_requests(packets, configured);
+        if requests.is_empty() {
+            return Err(anyhow!(Error::NoDumpRequest));
+        }
+
+        Ok(requests)
+    }
+
+    /// Open a single file or offer multiple files for merging
+    fn open_files(&mut self, mut paths: Vec<std::path::PathBuf>) {
+        if paths.len() > 1 {
+            // Multiple files are offered for merging
+            self.merge_window.open(paths);
+        } else if let Some(path) = paths.pop() {
+            self.open_file(&path);
+        }
+    }
+
+    /// Load a file and start the transfer if auto-start is enabled
+    fn open_file(&mut self, path: &std::path::Path) {
+        if self.load_file(path) && self.auto_start {
+            self.message_channel.0.send(Message::StartTransfer).ok();
+        }
+    }
+
+    /// Load a file and reset the transfer state, returns if the file is valid
+    fn load_file(&mut self, path: &std::path::Path) -> bool {
+        self.transfer_progress = 0.0;
+        self.transfer_state = TransferState::Idle;
+        match self.process_file(path) {
+            Ok(()) => {
+                self.error_message = None;
+                self.add_recent_file(path);
+                report::emit(report::Event::FileValidated {
+                    path: path.to_path_buf(),
+                    packets: self
+                        .file_packets
+                        .iter()
+                        .map(|packet| report::PacketInfo::new(packet))
+                        .collect(),
+                    warnings: self.file_warnings.iter().map(Error::to_string).collect(),
+                });
+                true
             }
-            Message::Error(error) => self.error_message = Some(error.to_string()),
+            Err(error) => {
+                self.error_message = Some(error.to_string());
+                report::emit(report::Event::FileRejected {
+                    path: path.to_path_buf(),
+                    error: error.to_string(),
+                });
+                false
+            }
+        }
+    }
+
+    /// Handle the keyboard shortcuts of the main window
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        let running = self.transfer_state == TransferState::Running;
+
+        // Enter and space activate the focused widget, if any
+        let no_focus = ctx.memory(|memory| memory.focused().is_none());
+
+        let (open, resend, reload, next_device, auto_start, start, cancel) = ctx.input_mut(|i| {
+            (
+                i.consume_shortcut(&OPEN_SHORTCUT),
+                // Must be checked before the reload shortcut, which matches as well
+                i.consume_shortcut(&RESEND_SHORTCUT),
+                i.consume_shortcut(&RELOAD_SHORTCUT),
+                i.consume_shortcut(&NEXT_DEVICE_SHORTCUT),
+                i.consume_shortcut(&AUTO_START_SHORTCUT),
+                no_focus
+                    && (i.consume_key(Modifiers::NONE, Key::Enter)
+                        || i.consume_key(Modifiers::NONE, Key::Space)),
+                running && i.consume_key(Modifiers::NONE, Key::Escape),
+            )
+        });
+
+        if auto_start {
+            self.auto_start = !self.auto_start;
+        }
+
+        if cancel {
+            self.cancel_transfer();
+        }
+
+        if running {
+            return;
+        }
+
+        if open {
+            self.open_file_dialog();
+        }
+
+        if resend {
+            self.resend_file();
+        }
+
+        if reload {
+            self.reload_file();
+        }
+
+        if next_device {
+            let next_device = {
+                let midi = self.midi.lock().unwrap();
+                let outputs = midi.get_outputs();
+                let index = outputs
+                    .iter()
+                    .position(|output| Some(&output.key) == self.selected_device.as_ref())
+                    .map_or(0, |index| (index + 1) % outputs.len());
+                outputs.get(index).map(|output| output.key.clone())
+            };
+            if let Some(device) = next_device {
+                self.message_channel
+                    .0
+                    .send(Message::SelectDevice(device))
+                    .ok();
+            }
+        }
+
+        if start && self.file_path.is_some() && self.error_message.is_none() {
+            self.message_channel.0.send(Message::StartTransfer).ok();
+        }
+    }
+
+    /// Load the current or most recent file again
+    fn reload_file(&mut self) {
+        if let Some(path) = self
+            .file_path
+            .clone()
+            .or(self.recent_files.first().cloned())
+        {
+            self.load_file(&path);
+        }
+    }
+
+    /// Send the current file again, validating it again if changed on disk
+    fn resend_file(&mut self) {
+        let Some(path) = self.file_path.clone() else {
+            return;
+        };
+
+        if self.file_changed(&path) {
+            log::info!("File {} changed, loading again.", path.display());
+            if !self.load_file(&path) {
+                return;
+            }
+        }
+
+        self.message_channel.0.send(Message::StartTransfer).ok();
+    }
+
+    /// Return if the file differs from the loaded one
+    ///
+    /// The hash is only compared if the modification time has changed.
+    fn file_changed(&self, path: &std::path::Path) -> bool {
+        let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified());
+        if modified.ok() == self.file_modified && self.file_modified.is_some() {
+            return false;
+        }
+
+        history::file_hash(path).map_or(true, |hash| hash != self.file_hash)
+    }
+
+    /// Move a file to the top of the recent files list
+    fn add_recent_file(&mut self, path: &std::path::Path) {
+        self.recent_files.retain(|recent| recent != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(RECENT_FILES_LIMIT);
+    }
+
+    /// Let the user choose files to open
+    fn open_file_dialog(&mut self) {
+        let mut dialog = rfd::FileDialog::new().add_filter("SysEx and MIDI files", &["syx", "mid"]);
+        if let Some(directory) = self
+            .file_path
+            .as_ref()
+            .or(self.recent_files.first())
+            .and_then(|path| path.parent())
+        {
+            dialog = dialog.set_directory(directory);
+        }
+
+        if let Some(paths) = dialog.pick_files() {
+            self.open_files(paths);
         }
     }
 
@@ -616,95 +2193,208 @@ impl App {
         self.file_type = None;
         self.file_size = 0;
         self.file_packet_count = 0;
+        self.file_warnings.clear();
+        self.file_packets.clear();
+        self.file_modified = None;
+        self.file_hash.clear();
 
         let file_type = FileType::from_path(path)?;
 
-        let mut file = std::fs::File::open(path)?;
-        let file_size = file.seek(std::io::SeekFrom::End(0))?;
-        file.rewind()?;
-
-        let mut packet_count = 0;
-
-        match file_type {
-            FileType::SysEx => {
-                let mut buf_reader = BufReader::new(file);
-                loop {
-                    let mut data = Vec::new();
-                    let data_length = buf_reader.read_until(midi::SYSEX_END_BYTE, &mut data)?;
-                    if data_length == 0 {
-                        // End of file
-                        break;
-                    }
-                    if data[0] != midi::SYSEX_START_BYTE {
-                        return Err(anyhow!(Error::NoStartByte));
-                    }
-                    if data[data_length - 1] != midi::SYSEX_END_BYTE {
-                        return Err(anyhow!(Error::NoEndByte));
-                    }
-                    packet_count += 1;
-                }
-            }
-            FileType::SMF => {
-                let content = std::fs::read(path)?;
-                let smf = midly::Smf::parse(&content)?;
-                for track in smf.tracks {
-                    for event in track {
-                        if let midly::TrackEventKind::SysEx(_) = event.kind {
-                            packet_count += 1;
-                        }
-                    }
-                }
-            }
-        }
+        let metadata = std::fs::metadata(path)?;
+        let file_size = metadata.len();
+        let file_hash = history::file_hash(path)?;
+        let packets = read_packets(path, &file_type)?;
 
-        if packet_count == 0 {
+        if packets.is_empty() {
             return Err(anyhow!(Error::NoPackets));
         }
 
+        let mut warnings = Vec::new();
+
+        for (index, packet) in packets.iter().enumerate() {
+            self.validate_packet(packet, index + 1, &mut warnings)?;
+        }
+
+        for (index, packet) in packets.iter().enumerate() {
+            if let checksum::Status::Invalid {
+                format,
+                expected,
+                found,
+            } = checksum::verify(packet)
+            {
+                let error = Error::Checksum {
+                    packet: index + 1,
+                    format,
+                    expected,
+                    found,
+                };
+                log::warn!("{}", error);
+                warnings.push(error);
+            }
+        }
+
         // File is valid, so set the info fields
         self.file_path = Some(path.to_path_buf());
         self.file_type = Some(file_type);
         self.file_size = file_size;
-        self.file_packet_count = packet_count;
+        self.file_packet_count = packets.len();
+        self.file_warnings = warnings;
+        self.file_packets = packets;
+        self.file_modified = metadata.modified().ok();
+        self.file_hash = file_hash;
 
         Ok(())
     }
+
+    /// Check a packet for status bytes inside the data
+    ///
+    /// Problems are added to the warnings or returned as error in strict mode.
+    fn validate_packet(
+        &self,
+        packet: &[u8],
+        packet_number: usize,
+        warnings: &mut Vec<Error>,
+    ) -> Result<()> {
+        if let Some((offset, value)) = midi::find_status_byte(packet) {
+            let error = Error::StatusByte {
+                packet: packet_number,
+                offset,
+                value,
+            };
+            if self.strict_mode {
+                return Err(anyhow!(error));
+            }
+            log::warn!("{}", error);
+            warnings.push(error);
+        }
+
+        Ok(())
+    }
+
+    /// Export the packets of the current file with another file type
+    fn export_file(&mut self, file_type: FileType) {
+        let Some(source_path) = &self.file_path else {
+            return;
+        };
+
+        let data = match file_type {
+            FileType::SysEx => Ok(convert::to_syx(&self.file_packets)),
+            FileType::SMF => convert::to_smf(
+                &self.file_packets,
+                std::time::Duration::from_millis(self.packet_interval),
+            ),
+        };
+
+        let file_name = source_path
+            .with_extension(file_type.extension())
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .unwrap_or_default()
+            .to_string();
+
+        if let Some(path) = save_file_dialog(&file_type, source_path.parent(), &file_name) {
+            match data.and_then(|data| Ok(std::fs::write(&path, data)?)) {
+                Ok(()) => log::info!("Exported to {}.", path.display()),
+                Err(error) => self.error_message = Some(error.to_string()),
+            }
+        }
+    }
+
+    /// Save each packet of the current file to a separate file
+    fn split_file(&mut self) {
+        let Some(source_path) = &self.file_path else {
+            return;
+        };
+
+        let stem = source_path
+            .file_stem()
+            .and_then(std::ffi::OsStr::to_str)
+            .unwrap_or("packet")
+            .to_string();
+
+        let mut dialog = rfd::FileDialog::new();
+        if let Some(directory) = source_path.parent() {
+            dialog = dialog.set_directory(directory);
+        }
+
+        if let Some(directory) = dialog.pick_folder() {
+            match split::split(&self.file_packets, &directory, &stem) {
+                Ok(count) => log::info!("Split into {} files in {}.", count, directory.display()),
+                Err(error) => self.error_message = Some(error.to_string()),
+            }
+        }
+    }
+
+    /// Return the number of packets with checksum errors
+    fn checksum_error_count(&self) -> usize {
+        self.file_warnings
+            .iter()
+            .filter(|warning| matches!(warning, Error::Checksum { .. }))
+            .count()
+    }
 }
 
-/// Show combobox with devices
+/// Show combobox with devices and connection status
+///
+/// A selected device that is currently not available is kept in the list, so
+/// that it is connected again when it returns. Devices are selected by their key.
 pub fn device_selection(
     ui: &mut egui::Ui,
-    devices: &[String],
+    label: &str,
+    devices: &[midi::Port],
     selected_device: Option<String>,
+    connected: bool,
     message_sender: &std::sync::mpsc::Sender<Message>,
+    select_message: fn(String) -> Message,
 ) {
     let mut device_list = Vec::new();
     let mut device_index = 0;
+    let mut selected_port = None;
 
-    if !devices.is_empty() {
-        for (index, device) in devices.iter().enumerate() {
-            device_list.push((&device).to_string());
+    for (index, device) in devices.iter().enumerate() {
+        device_list.push(device.label.clone());
 
-            if selected_device.is_some() && selected_device.as_ref().unwrap() == device {
-                device_index = index;
-            }
+        if selected_device
+            .as_ref()
+            .is_some_and(|selected| *selected == device.key || *selected == device.name)
+        {
+            device_index = index;
+            selected_port = Some(device);
         }
     }
 
+    if let Some(selected_device) = &selected_device
+        && selected_port.is_none()
+    {
+        device_index = device_list.len();
+        device_list.push(format!("{} (not available)", selected_device));
+    }
+
     let device_count = device_list.len();
 
     ui.horizontal(|ui| {
         ui.vertical(|ui| {
+            ui.set_width(50.0);
             ui.add_space(5.0);
-            ui.label("Device:");
+            ui.label(label);
         });
 
+        let (color, status) = if connected {
+            (egui::Color32::GREEN, "Connected")
+        } else if selected_device.is_some() {
+            (egui::Color32::RED, "Not connected")
+        } else {
+            (egui::Color32::GRAY, "Nothing selected")
+        };
+        ui.label(egui::RichText::new("●").color(color))
+            .on_hover_text(status);
+
         ui.scope(|ui| {
             if device_list.is_empty() {
                 ui.disable();
             }
 
-            let combo_box = egui::ComboBox::from_id_salt("device_list")
+            let combo_box = egui::ComboBox::from_id_salt(label)
                 .width(ui.available_width())
                 .show_index(ui, &mut device_index, device_list.len(), |i| {
                     if device_count > 0 {
@@ -714,37 +2404,128 @@ pub fn device_selection(
                     }
                 });
 
-            if combo_box.changed() && !devices.is_empty() {
-                for (index, device) in devices.iter().enumerate() {
-                    let d = devices.iter().find(|&x| x == device);
-                    if d.is_some() && index == device_index {
-                        message_sender
-                            .send(Message::SelectDevice(device.to_string()))
-                            .ok();
-                    }
+            let combo_box = match selected_port {
+                Some(port) => {
+                    combo_box.on_hover_text(format!("{}\nPort ID: {}", port.name, port.id))
                 }
+                None => combo_box,
+            };
+
+            if combo_box.changed()
+                && let Some(device) = devices.get(device_index)
+            {
+                message_sender.send(select_message(device.key.clone())).ok();
             };
         });
     });
 }
 
+/// Show a native dialog for saving a file, returns the selected path
+pub fn save_file_dialog(
+    file_type: &FileType,
+    directory: Option<&std::path::Path>,
+    file_name: &str,
+) -> Option<std::path::PathBuf> {
+    let mut dialog = rfd::FileDialog::new()
+        .add_filter(file_type.description(), &[file_type.extension()])
+        .set_file_name(file_name);
+    if let Some(directory) = directory {
+        dialog = dialog.set_directory(directory);
+    }
+
+    dialog.save_file()
+}
+
 /// Sends the SysEx data, called in separate thread
+///
+/// With reply pacing, each packet is sent after the reply to the previous one
+/// instead of waiting for the packet interval.
 fn send_sysex(
-    file_path: std::path::PathBuf,
+    packets: &[Vec<u8>],
     midi: Arc<Mutex<midi::MidiConnector>>,
     packet_interval: std::time::Duration,
+    pacing: Option<&pacing::ReplyPacing>,
     message_sender: std::sync::mpsc::Sender<Message>,
-    receiver: std::sync::mpsc::Receiver<bool>,
+    receiver: &std::sync::mpsc::Receiver<bool>,
 ) -> Result<bool> {
-    let file_type = FileType::from_path(file_path.as_path())?;
+    let pacing = pacing.map(|pacing| (pacing, midi.lock().unwrap().subscribe_input()));
+
+    for (index, packet) in packets.iter().enumerate() {
+        if let Some((pacing, input_receiver)) = &pacing {
+            let mut attempt = 0;
+            loop {
+                // Ignore replies to earlier packets
+                input_receiver.try_iter().for_each(drop);
+
+                midi::send(&midi, packet)?;
+
+                match pacing.wait(input_receiver, receiver) {
+                    pacing::Reply::Received => break,
+                    pacing::Reply::Cancelled => return Ok(false),
+                    pacing::Reply::Timeout if attempt < pacing.retries => {
+                        attempt += 1;
+                        log::warn!("No reply to packet {}, sending again.", index + 1);
+                    }
+                    pacing::Reply::Timeout => {
+                        return Err(anyhow!(Error::NoReply { packet: index + 1 }));
+                    }
+                }
+            }
+        } else {
+            midi::send(&midi, packet)?;
+        }
+
+        message_sender.send(Message::PacketTransferred(index + 1))?;
+
+        if pacing.is_none() {
+            std::thread::sleep(packet_interval);
+        }
+
+        if receiver.try_recv().is_ok() {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Return the estimated duration for sending the packets
+///
+/// Each packet takes at least the packet interval or the time needed at the
+/// standard MIDI data rate, whichever is longer.
+fn estimate_duration(packets: &[Vec<u8>], packet_interval: Duration) -> Duration {
+    packets
+        .iter()
+        .map(|packet| {
+            packet_interval.max(Duration::from_secs_f64(
+                packet.len() as f64 / midi::MIDI_BYTES_PER_SECOND,
+            ))
+        })
+        .sum()
+}
+
+/// Format a duration as seconds or minutes and seconds
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds < 60.0 {
+        format!("{:.1} s", seconds)
+    } else {
+        format!(
+            "{}:{:02} min",
+            duration.as_secs() / 60,
+            duration.as_secs() % 60
+        )
+    }
+}
+
+/// Read all packets from a file, each including start and end byte
+fn read_packets(path: &std::path::Path, file_type: &FileType) -> Result<Vec<Vec<u8>>> {
+    let mut packets = Vec::new();
 
     match file_type {
         FileType::SysEx => {
-            let file = std::fs::File::open(file_path)?;
-
+            let file = std::fs::File::open(path)?;
             let mut buf_reader = BufReader::new(file);
-            let mut packet_count = 0;
-
             loop {
                 let mut data = Vec::new();
                 let data_length = buf_reader.read_until(midi::SYSEX_END_BYTE, &mut data)?;
@@ -752,51 +2533,37 @@ fn send_sysex(
                     // End of file
                     break;
                 }
-                packet_count += 1;
-                message_sender.send(Message::PacketTransferred(packet_count))?;
-
-                midi.lock().unwrap().send(&data);
-
-                std::thread::sleep(packet_interval);
-
-                if receiver.try_recv().is_ok() {
-                    return Ok(false);
+                if data[0] != midi::SYSEX_START_BYTE {
+                    return Err(anyhow!(Error::NoStartByte));
+                }
+                if data[data_length - 1] != midi::SYSEX_END_BYTE {
+                    return Err(anyhow!(Error::NoEndByte));
                 }
+                packets.push(data);
             }
         }
         FileType::SMF => {
-            let content = std::fs::read(file_path)?;
+            let content = std::fs::read(path)?;
             let smf = midly::Smf::parse(&content)?;
-            let mut packet_count = 0;
-
             for track in smf.tracks {
                 for event in track {
                     if let midly::TrackEventKind::SysEx(data) = event.kind {
-                        let mut message = vec![0xF0];
-                        message.extend_from_slice(data);
-                        packet_count += 1;
-                        message_sender.send(Message::PacketTransferred(packet_count))?;
-
-                        midi.lock().unwrap().send(&message);
-
-                        std::thread::sleep(packet_interval);
-
-                        if receiver.try_recv().is_ok() {
-                            return Ok(false);
-                        }
+                        let mut packet = vec![midi::SYSEX_START_BYTE];
+                        packet.extend_from_slice(data);
+                        packets.push(packet);
                     }
                 }
             }
         }
     }
 
-    Ok(true)
+    Ok(packets)
 }
 
 ////////////////////////////////////////////////////////////////////////////////
 
 /// Errors with associated messages
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Error {
     /// Sysex start byte not found in file
     NoStartByte,
@@ -806,6 +2573,66 @@ pub enum Error {
 
     /// File does not contain any packets
     NoPackets,
+
+    /// Status byte found inside a packet
+    StatusByte {
+        /// Packet number, starting at 1
+        packet: usize,
+
+        /// Offset of the byte within the packet
+        offset: usize,
+
+        /// Value of the byte
+        value: u8,
+    },
+
+    /// Checksum of a packet does not match
+    Checksum {
+        /// Packet number, starting at 1
+        packet: usize,
+
+        /// Recognized checksum format
+        format: checksum::Format,
+
+        /// Calculated checksum
+        expected: u8,
+
+        /// Checksum found in the packet
+        found: u8,
+    },
+
+    /// Transfer refused because of checksum errors
+    ChecksumBlocked,
+
+    /// No MIDI input selected for receiving replies
+    NoInput,
+
+    /// Configured dump request is not a valid SysEx message
+    InvalidDumpRequest,
+
+    /// No dump request available for verification
+    NoDumpRequest,
+
+    /// File was modified since a previous transfer
+    FileChanged,
+
+    /// Transfer requested without a loaded file
+    NoFile,
+
+    /// Output device of a previous transfer not found
+    DeviceNotAvailable {
+        /// Name of the device
+        name: String,
+    },
+
+    /// Configured reply pattern is not valid hex notation
+    InvalidReplyPattern,
+
+    /// No reply of the device within the timeout
+    NoReply {
+        /// Number of the packet, starting at 1
+        packet: usize,
+    },
 }
 
 impl std::error::Error for Error {}
@@ -820,7 +2647,88 @@ impl std::fmt::Display for Error {
                     format!("Start byte 0x{:02X} not found.", midi::SYSEX_START_BYTE),
                 Self::NoEndByte => format!("End byte 0x{:02X} not found.", midi::SYSEX_END_BYTE),
                 Self::NoPackets => "No sysex packets found.".to_string(),
+                Self::StatusByte {
+                    packet,
+                    offset,
+                    value,
+                } => format!(
+                    "Status byte 0x{:02X} at offset {} in packet {}.",
+                    value, offset, packet
+                ),
+                Self::Checksum {
+                    packet,
+                    format,
+                    expected,
+                    found,
+                } => format!(
+                    "{} checksum 0x{:02X} in packet {} should be 0x{:02X}.",
+                    format, found, packet, expected
+                ),
+                Self::ChecksumBlocked => "File contains checksum errors.".to_string(),
+                Self::NoInput => "No MIDI input selected.".to_string(),
+                Self::InvalidDumpRequest => "Invalid dump request.".to_string(),
+                Self::NoDumpRequest => "No dump request known for this file.".to_string(),
+                Self::FileChanged => "File has changed since the transfer.".to_string(),
+                Self::NoFile => "No file loaded.".to_string(),
+                Self::DeviceNotAvailable { name } => format!("Device {} not available.", name),
+                Self::InvalidReplyPattern => "Invalid reply pattern.".to_string(),
+                Self::NoReply { packet } => format!("No reply to packet {}.", packet),
             }
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return parsed arguments from strings
+    fn parse(args: &[&str]) -> Arguments {
+        Arguments::parse(args.iter().map(std::ffi::OsString::from))
+    }
+
+    #[test]
+    fn arguments_json() {
+        let arguments = parse(&["--json", "a.syx"]);
+        assert!(arguments.json);
+        assert_eq!(
+            arguments.file_paths,
+            vec![std::path::PathBuf::from("a.syx")]
+        );
+    }
+
+    #[test]
+    fn arguments_files() {
+        let arguments = parse(&["a.syx", "b.mid"]);
+        assert!(!arguments.json);
+        assert_eq!(
+            arguments.file_paths,
+            vec![
+                std::path::PathBuf::from("a.syx"),
+                std::path::PathBuf::from("b.mid")
+            ]
+        );
+    }
+
+    #[test]
+    fn arguments_dash_file() {
+        let arguments = parse(&["-foo.syx"]);
+        assert_eq!(
+            arguments.file_paths,
+            vec![std::path::PathBuf::from("-foo.syx")]
+        );
+    }
+
+    #[test]
+    fn arguments_separator() {
+        let arguments = parse(&["--json", "--", "--json", "--"]);
+        assert!(arguments.json);
+        assert_eq!(
+            arguments.file_paths,
+            vec![
+                std::path::PathBuf::from("--json"),
+                std::path::PathBuf::from("--")
+            ]
+        );
+    }
+}
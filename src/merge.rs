@@ -0,0 +1,198 @@
+//! Module containing the window for merging several files into one
+
+use eframe::egui;
+
+use crate::FileType;
+
+/// Size of the merge window
+const WINDOW_SIZE: egui::Vec2 = egui::vec2(440.0, 320.0);
+
+/// Merge window state
+#[derive(Default)]
+pub struct MergeWindow {
+    /// Window is shown
+    pub open: bool,
+
+    /// Files in merge order
+    files: Vec<MergeFile>,
+
+    /// Result of the last action
+    status: Option<String>,
+}
+
+/// File to be merged
+struct MergeFile {
+    /// Path of the file
+    path: std::path::PathBuf,
+
+    /// Packets of the file or error message if invalid
+    packets: Result<Vec<Vec<u8>>, String>,
+}
+
+/// Actions requested from the merge window
+pub enum Action {
+    /// Load the merged file, without auto-start
+    Load(std::path::PathBuf),
+}
+
+impl MergeWindow {
+    /// Open the window with a list of files, sorted by name
+    pub fn open(&mut self, mut paths: Vec<std::path::PathBuf>) {
+        paths.sort_by_key(|path| path.file_name().map(|name| name.to_ascii_lowercase()));
+
+        self.open = true;
+        self.files.clear();
+        self.status = None;
+        self.add_files(paths);
+    }
+
+    /// Add files to the end of the list
+    fn add_files(&mut self, paths: Vec<std::path::PathBuf>) {
+        for path in paths {
+            let packets = FileType::from_path(&path)
+                .and_then(|file_type| crate::read_packets(&path, &file_type))
+                .map_err(|error| error.to_string());
+            self.files.push(MergeFile { path, packets });
+        }
+    }
+
+    /// Show the window if open, returns the requested action
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<Action> {
+        if !self.open {
+            return None;
+        }
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("merge_window"),
+            egui::ViewportBuilder::default()
+                .with_title("Merge Files")
+                .with_inner_size(WINDOW_SIZE)
+                .with_drag_and_drop(true),
+            |ui, _class| {
+                if ui.ctx().input(|i| i.viewport().close_requested()) {
+                    self.open = false;
+                }
+
+                let dropped_files = ui.ctx().input(|i| i.raw.dropped_files.clone());
+                self.add_files(
+                    dropped_files
+                        .into_iter()
+                        .map(|file| file.path().to_path_buf())
+                        .collect(),
+                );
+
+                egui::CentralPanel::default()
+                    .show(ui, |ui| self.ui(ui))
+                    .inner
+            },
+        )
+    }
+
+    /// Contents of the merge window
+    fn ui(&mut self, ui: &mut egui::Ui) -> Option<Action> {
+        let mut action = None;
+
+        ui.label("Files are merged in this order. Drop more files here to add them.");
+
+        ui.horizontal(|ui| {
+            if ui.button("Add files…").clicked()
+                && let Some(paths) = rfd::FileDialog::new()
+                    .add_filter("SysEx and MIDI files", &["syx", "mid"])
+                    .pick_files()
+            {
+                self.add_files(paths);
+            }
+
+            let valid = !self.files.is_empty() && self.files.iter().all(|f| f.packets.is_ok());
+            if ui
+                .add_enabled(valid, egui::Button::new("Save merged…"))
+                .on_hover_text("Save all packets as one SysEx file and open it")
+                .clicked()
+            {
+                action = self.save();
+            }
+        });
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        ui.separator();
+
+        let mut move_up = None;
+        let mut remove = None;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("merge_files").striped(true).show(ui, |ui| {
+                for (index, file) in self.files.iter().enumerate() {
+                    ui.label(format!("{}.", index + 1));
+                    ui.label(
+                        file.path
+                            .file_name()
+                            .and_then(std::ffi::OsStr::to_str)
+                            .unwrap_or("Invalid filename"),
+                    )
+                    .on_hover_text(file.path.to_str().unwrap_or("Invalid filename"));
+                    match &file.packets {
+                        Ok(packets) => {
+                            ui.label(format!("{} packets", packets.len()));
+                        }
+                        Err(error) => {
+                            ui.label(egui::RichText::new(error).color(egui::Color32::RED));
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.add_enabled(index > 0, egui::Button::new("▲")).clicked() {
+                            move_up = Some(index);
+                        }
+                        if ui
+                            .add_enabled(index + 1 < self.files.len(), egui::Button::new("▼"))
+                            .clicked()
+                        {
+                            move_up = Some(index + 1);
+                        }
+                        if ui.button("✖").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        });
+
+        if let Some(index) = move_up {
+            self.files.swap(index - 1, index);
+        }
+        if let Some(index) = remove {
+            self.files.remove(index);
+        }
+
+        action
+    }
+
+    /// Save the merged packets to a file chosen by the user
+    fn save(&mut self) -> Option<Action> {
+        let directory = self.files.first().and_then(|file| file.path.parent());
+        let path = crate::save_file_dialog(&FileType::SysEx, directory, "merged.syx")?;
+
+        let data: Vec<u8> = self
+            .files
+            .iter()
+            .filter_map(|file| file.packets.as_ref().ok())
+            .flatten()
+            .flatten()
+            .copied()
+            .collect();
+
+        match std::fs::write(&path, data) {
+            Ok(()) => {
+                self.status = Some(format!("Saved to {}.", path.display()));
+                Some(Action::Load(path))
+            }
+            Err(error) => {
+                self.status = Some(format!("Error: {}", error));
+                None
+            }
+        }
+    }
+}
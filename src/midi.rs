@@ -1,7 +1,12 @@
 //! Module containing the MIDI-related code
 
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
 use anyhow::{Result, anyhow};
-use midir::{MidiOutput, MidiOutputConnection};
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+
+use crate::{jackmidi, rawmidi, rtpmidi};
 
 /// Sysex message start byte
 pub const SYSEX_START_BYTE: u8 = 0xF0;
@@ -9,34 +14,323 @@ pub const SYSEX_START_BYTE: u8 = 0xF0;
 /// Sysex message end byte
 pub const SYSEX_END_BYTE: u8 = 0xF7;
 
+/// Transfer rate of a standard MIDI DIN connection
+pub const MIDI_BYTES_PER_SECOND: f64 = 3125.0;
+
+/// Minimum number of matching characters for pairing an input with an output
+const PAIRING_MIN_LENGTH: usize = 3;
+
+/// Returns offset and value of the first byte inside a packet that is not a data byte
+///
+/// The start byte and a trailing end byte are excluded from the check.
+/// System real-time bytes (`0xF8` to `0xFF`) may occur inside SysEx and are
+/// not reported.
+pub fn find_status_byte(packet: &[u8]) -> Option<(usize, u8)> {
+    let end = match packet.last() {
+        Some(&SYSEX_END_BYTE) => packet.len() - 1,
+        _ => packet.len(),
+    };
+
+    packet
+        .iter()
+        .enumerate()
+        .take(end)
+        .skip(1)
+        .find(|(_, byte)| (0x80..=0xF6).contains(*byte))
+        .map(|(offset, byte)| (offset, *byte))
+}
+
+/// Parse bytes in hex notation like `F0 41 10 F7` or `F04110F7`
+pub fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.is_ascii() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&digits[index..index + 2], 16).ok())
+        .collect()
+}
+
+/// Format bytes in hex notation
+pub fn format_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Return a port name without direction words for pairing inputs and outputs
+///
+/// Covers names like `XYZ MIDI 1 In`/`XYZ MIDI 1 Out` and `MIDIIN2 (XYZ)`/`MIDIOUT2 (XYZ)`.
+fn pairing_key(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !matches!(*word, "in" | "out" | "input" | "output"))
+        .map(|word| word.replace("midiin", "midi").replace("midiout", "midi"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Return a port name without the client and port numbers appended by ALSA
+///
+/// ALSA names look like `XYZ:XYZ MIDI 1 24:0`, the numbers can change between boots.
+fn stable_name(name: &str) -> &str {
+    let is_number = |text: &str| !text.is_empty() && text.chars().all(|c| c.is_ascii_digit());
+
+    match name.rsplit_once(' ') {
+        Some((stable, numbers))
+            if numbers
+                .split_once(':')
+                .is_some_and(|(client, port)| is_number(client) && is_number(port)) =>
+        {
+            stable
+        }
+        _ => name,
+    }
+}
+
+/// Port found by a scan
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Port {
+    /// Backend identifier, only valid while the port exists
+    pub id: String,
+
+    /// Name as reported by the backend
+    pub name: String,
+
+    /// Name for display, numbered if several ports have the same name
+    pub label: String,
+
+    /// Identifier for persisting a selection, stays the same across restarts
+    pub key: String,
+}
+
+/// Build the port list from identifiers and names in backend order
+///
+/// Ports with the same name are numbered by their order, e.g. `XYZ #2`.
+fn port_list(ports: Vec<(String, String)>) -> Vec<Port> {
+    let stable_names: Vec<&str> = ports.iter().map(|(_, name)| stable_name(name)).collect();
+
+    ports
+        .iter()
+        .enumerate()
+        .map(|(index, (id, name))| {
+            let stable = stable_names[index];
+            let total = stable_names
+                .iter()
+                .filter(|other| **other == stable)
+                .count();
+            let number = stable_names[..=index]
+                .iter()
+                .filter(|other| **other == stable)
+                .count();
+
+            Port {
+                id: id.clone(),
+                name: name.clone(),
+                label: if total > 1 {
+                    format!("{} #{}", name, number)
+                } else {
+                    name.clone()
+                },
+                key: if number > 1 {
+                    format!("{} #{}", stable, number)
+                } else {
+                    stable.to_string()
+                },
+            }
+        })
+        .collect()
+}
+
+/// Return the port matching a key or a name
+///
+/// Names are accepted for selections stored by earlier versions. A name with
+/// outdated ALSA numbers still matches the port by its key.
+fn find_port<'a>(ports: &'a [Port], key: &str) -> Option<&'a Port> {
+    ports
+        .iter()
+        .find(|port| port.key == key)
+        .or_else(|| ports.iter().find(|port| port.name == key))
+        .or_else(|| ports.iter().find(|port| port.key == stable_name(key)))
+}
+
+/// Message received on the input
+#[derive(Debug, Clone)]
+pub struct InputMessage {
+    /// Timestamp in microseconds as provided by the backend
+    pub timestamp: u64,
+
+    /// Message bytes, SysEx messages are complete including start and end byte
+    pub data: Vec<u8>,
+}
+
+/// Backend providing the MIDI ports
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Backend {
+    /// Native backend of the platform, ALSA on Linux
+    #[default]
+    Native,
+
+    /// JACK, only available when built with the `jack` feature
+    Jack,
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Native if cfg!(target_os = "linux") => write!(f, "ALSA"),
+            Self::Native => write!(f, "System"),
+            Self::Jack => write!(f, "JACK"),
+        }
+    }
+}
+
+/// Send a message on the output of a shared connector
+///
+/// Raw MIDI devices are written in chunks as buffer space becomes available.
+/// The lock is only held while writing a chunk, so the UI isn't blocked during
+/// long packets. Other outputs are sent to directly.
+pub fn send(midi: &Mutex<MidiConnector>, message: &[u8]) -> Result<()> {
+    let mut written = 0;
+
+    loop {
+        {
+            let mut connector = midi.lock().unwrap();
+            let Some(Output::Raw(output)) = connector.output.as_mut() else {
+                if written > 0 {
+                    return Err(anyhow!("MIDI output disconnected during a packet."));
+                }
+                return connector.send(message);
+            };
+            if written < message.len() {
+                written += output.write(&message[written..])?;
+            } else if output.is_drained()? {
+                return Ok(());
+            }
+        }
+
+        std::thread::sleep(rawmidi::POLL_INTERVAL);
+    }
+}
+
+/// Connection to an output
+enum Output {
+    /// Port of the native backend used by midir
+    Midir(MidiOutputConnection),
+
+    /// ALSA raw MIDI device
+    Raw(rawmidi::Output),
+
+    /// JACK port
+    Jack(jackmidi::Connection),
+
+    /// Network MIDI session
+    Rtp(rtpmidi::Session),
+}
+
+impl Output {
+    /// Sends a message
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        match self {
+            Self::Midir(conn) => conn
+                .send(message)
+                .map_err(|error| anyhow!("MIDI send error: {}", error)),
+            Self::Raw(conn) => conn.send(message),
+            Self::Jack(conn) => conn.send(message),
+            Self::Rtp(session) => session.send(message),
+        }
+    }
+}
+
+/// Connection to an input
+///
+/// The connections are only kept, received messages are passed on by callbacks.
+enum Input {
+    /// Port of the native backend used by midir
+    Midir {
+        _connection: MidiInputConnection<()>,
+    },
+
+    /// JACK port
+    Jack { _connection: jackmidi::Connection },
+}
+
 /// Container for connections and state
 pub struct MidiConnector {
+    /// Backend providing the ports
+    backend: Backend,
+
+    /// JACK client while the JACK backend is selected
+    jack: Option<Arc<jackmidi::Client>>,
+
+    /// Last error registering the JACK client, logged only once
+    jack_error: Option<String>,
+
     /// Objects used for port scanning
     scan_output: Option<MidiOutput>,
 
-    /// Vector of port names that are usable as outputs
-    outputs_list: Vec<String>,
+    /// Object used for input port scanning
+    scan_input: Option<MidiInput>,
+
+    /// Ports that are usable as outputs
+    outputs_list: Vec<Port>,
+
+    /// Ports that are usable as inputs
+    inputs_list: Vec<Port>,
+
+    /// Raw MIDI devices are listed as outputs
+    raw_outputs: bool,
+
+    /// Addresses of network MIDI peers listed as outputs
+    network_peers: Vec<String>,
 
     /// Onput connection
-    output: Option<MidiOutputConnection>,
+    output: Option<Output>,
+
+    /// Output port
+    output_port: Option<Port>,
+
+    /// Network port selected as output while the session is being started
+    connecting: Option<Port>,
+
+    /// Input connection
+    input: Option<Input>,
 
-    /// Name of the Output port
-    output_name: String,
+    /// Input port
+    input_port: Option<Port>,
+
+    /// Senders for passing received messages to subscribers
+    input_subscribers: Arc<Mutex<Vec<Sender<InputMessage>>>>,
 }
 
 impl MidiConnector {
     /// Constructs a new instance
     pub fn new() -> Self {
         Self {
+            backend: Backend::Native,
+            jack: None,
+            jack_error: None,
             scan_output: None,
+            scan_input: None,
             outputs_list: Vec::new(),
+            inputs_list: Vec::new(),
+            raw_outputs: false,
+            network_peers: Vec::new(),
             output: None,
-            output_name: String::new(),
+            output_port: None,
+            connecting: None,
+            input: None,
+            input_port: None,
+            input_subscribers: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
-    /// Scan the ports and return if anything has changed since the last scan
-    pub fn scan_ports(&mut self) -> bool {
+    /// Create the objects used for port scanning if not present
+    fn create_scan_clients(&mut self) {
         if self.scan_output.is_none() {
             match MidiOutput::new(&(env!("CARGO_PKG_NAME").to_owned() + " scan output")) {
                 Ok(output) => {
@@ -48,78 +342,603 @@ impl MidiConnector {
             }
         }
 
+        if self.scan_input.is_none() {
+            match MidiInput::new(&(env!("CARGO_PKG_NAME").to_owned() + " scan input")) {
+                Ok(mut input) => {
+                    input.ignore(Ignore::None);
+                    self.scan_input = Some(input);
+                }
+                Err(error) => {
+                    log::error!("MIDI scan input error: {}", error);
+                }
+            }
+        }
+    }
+
+    /// Scan the ports and return if anything has changed since the last scan
+    ///
+    /// Ports are compared by name and identifier, so replacing a device by
+    /// another one is detected as well. Connections to ports that have
+    /// disappeared are closed.
+    pub fn scan_ports(&mut self) -> bool {
+        let (outputs, inputs) = self.backend_ports();
+
         let mut ports_changed = false;
 
-        if let Some(output) = &self.scan_output {
-            let mut outputs_list = Vec::new();
-            for port in output.ports().iter() {
-                let port_name = output.port_name(port).unwrap();
-                outputs_list.push(port_name);
+        if let Some(mut outputs) = outputs {
+            if self.raw_outputs {
+                outputs.extend(rawmidi::outputs());
             }
-            ports_changed = self.outputs_list.len() != outputs_list.len();
+            outputs.extend(self.network_peers.iter().map(|peer| rtpmidi::port(peer)));
+            let outputs_list = port_list(outputs);
+            ports_changed = self.outputs_list != outputs_list;
             self.outputs_list = outputs_list;
         }
 
+        if let Some(inputs) = inputs {
+            let inputs_list = port_list(inputs);
+            ports_changed |= self.inputs_list != inputs_list;
+            self.inputs_list = inputs_list;
+        }
+
+        if self.close_ended_session() {
+            ports_changed = true;
+        }
+
+        if ports_changed {
+            if self.output.is_some()
+                && let Some(port) = &self.output_port
+                && !self.outputs_list.iter().any(|other| other.id == port.id)
+            {
+                log::warn!("MIDI output {} disappeared.", port.name);
+                self.output = None;
+            }
+            if self.input.is_some()
+                && let Some(port) = &self.input_port
+                && !self.inputs_list.iter().any(|other| other.id == port.id)
+            {
+                log::warn!("MIDI input {} disappeared.", port.name);
+                self.input = None;
+            }
+        }
+
         ports_changed
     }
 
-    /// Sends a message
-    pub fn send(&mut self, message: &[u8]) {
-        if let Some(conn) = self.output.as_mut() {
-            conn.send(message).ok();
+    /// Close connections to ports that are not available anymore
+    ///
+    /// Returns if a connection was closed. Unlike a scan, the port lists are
+    /// not updated.
+    pub fn check_connections(&mut self) -> bool {
+        if self.backend == Backend::Native {
+            self.create_scan_clients();
         }
-    }
 
-    /// Return a vector of outputs
-    pub fn get_outputs(&self) -> &Vec<String> {
-        &self.outputs_list
+        let mut closed = self.close_ended_session();
+
+        if self.output.is_some()
+            && let Some(port) = &self.output_port
+            && !self.port_exists(&port.id, true)
+        {
+            log::warn!("MIDI output {} disappeared.", port.name);
+            self.output = None;
+            closed = true;
+        }
+
+        if self.input.is_some()
+            && let Some(port) = &self.input_port
+            && !self.port_exists(&port.id, false)
+        {
+            log::warn!("MIDI input {} disappeared.", port.name);
+            self.input = None;
+            closed = true;
+        }
+
+        closed
     }
 
-    /// Select the output
-    pub fn select_output(&mut self, output_name: String) -> Result<()> {
-        if self.output.is_some() {
+    /// Close a network session that has ended, returns if closed
+    ///
+    /// The port is cleared as well, so that it is selected again.
+    fn close_ended_session(&mut self) -> bool {
+        if let Some(Output::Rtp(session)) = &self.output
+            && session.ended()
+        {
             self.output = None;
-            self.output_name = String::new();
+            self.output_port = None;
+            return true;
         }
 
-        if self.scan_output.is_none() {
-            match MidiOutput::new(&(env!("CARGO_PKG_NAME").to_owned() + " scan output")) {
-                Ok(output) => {
-                    self.scan_output = Some(output);
+        false
+    }
+
+    /// Return identifiers and names of the outputs and inputs of the backend
+    ///
+    /// `None` is returned if the ports could not be listed.
+    #[allow(clippy::type_complexity)]
+    fn backend_ports(&mut self) -> (Option<Vec<(String, String)>>, Option<Vec<(String, String)>>) {
+        match self.backend {
+            Backend::Native => {
+                self.create_scan_clients();
+                let outputs = self.scan_output.as_ref().map(|output| {
+                    output
+                        .ports()
+                        .iter()
+                        .filter_map(|port| Some((port.id(), output.port_name(port).ok()?)))
+                        .collect()
+                });
+                let inputs = self.scan_input.as_ref().map(|input| {
+                    input
+                        .ports()
+                        .iter()
+                        .filter_map(|port| Some((port.id(), input.port_name(port).ok()?)))
+                        .collect()
+                });
+                (outputs, inputs)
+            }
+            Backend::Jack => match self.jack_client() {
+                Some(client) => (Some(client.outputs()), Some(client.inputs())),
+                None => (Some(Vec::new()), Some(Vec::new())),
+            },
+        }
+    }
+
+    /// Return if a port still exists, ports that can not be checked are assumed to exist
+    fn port_exists(&self, id: &str, output: bool) -> bool {
+        if output && rawmidi::is_raw_id(id) {
+            return rawmidi::outputs().iter().any(|(raw_id, _)| raw_id == id);
+        }
+
+        if output && rtpmidi::is_rtp_id(id) {
+            return self
+                .network_peers
+                .iter()
+                .any(|peer| peer == rtpmidi::address(id));
+        }
+
+        match self.backend {
+            Backend::Native if output => self
+                .scan_output
+                .as_ref()
+                .is_none_or(|scan_output| scan_output.find_port_by_id(id).is_some()),
+            Backend::Native => self
+                .scan_input
+                .as_ref()
+                .is_none_or(|scan_input| scan_input.find_port_by_id(id).is_some()),
+            Backend::Jack => self
+                .jack
+                .as_ref()
+                .is_some_and(|client| client.port_exists(id)),
+        }
+    }
+
+    /// Return the JACK client, registering it if required
+    ///
+    /// A client of a server that shut down is replaced together with its connections.
+    fn jack_client(&mut self) -> Option<Arc<jackmidi::Client>> {
+        if self.jack.as_ref().is_some_and(|client| !client.is_alive()) {
+            log::warn!("JACK server shut down.");
+            if matches!(self.output, Some(Output::Jack(_))) {
+                self.output = None;
+            }
+            if matches!(self.input, Some(Input::Jack { .. })) {
+                self.input = None;
+            }
+            self.jack = None;
+        }
+
+        if self.jack.is_none() {
+            match jackmidi::Client::new(self.input_subscribers.clone()) {
+                Ok(client) => {
+                    self.jack = Some(client);
+                    self.jack_error = None;
                 }
                 Err(error) => {
-                    log::error!("MIDI scan output error: {}", error);
+                    let error = error.to_string();
+                    if self.jack_error.as_ref() != Some(&error) {
+                        log::error!("{}", error);
+                    }
+                    self.jack_error = Some(error);
                 }
             }
         }
 
-        let output = self.scan_output.as_ref().unwrap();
-
-        for port in output.ports().iter() {
-            let port_name = output.port_name(port).unwrap();
-            if port_name == output_name {
-                log::info!("MIDI output connected to port {}", port_name);
-                let scan_output = self
-                    .scan_output
-                    .take()
-                    .unwrap()
-                    .connect(port, "SysEx Drop Output");
-                if let Ok(scan_output) = scan_output {
-                    self.output = Some(scan_output);
-                    self.output_name = port_name;
-                } else {
-                    return Err(anyhow!("MIDI connection error."));
-                }
-                break;
-            }
+        self.jack.clone()
+    }
+
+    /// Select the backend, closing the current connections
+    ///
+    /// The ports are listed with the next scan.
+    pub fn set_backend(&mut self, backend: Backend) {
+        if backend == self.backend {
+            return;
+        }
+
+        self.output = None;
+        self.output_port = None;
+        self.connecting = None;
+        self.input = None;
+        self.input_port = None;
+        self.jack = None;
+        self.jack_error = None;
+        self.backend = backend;
+
+        log::info!("MIDI backend {} selected.", backend);
+    }
+
+    /// Sends a message
+    pub fn send(&mut self, message: &[u8]) -> Result<()> {
+        let Some(conn) = self.output.as_mut() else {
+            return Err(anyhow!("MIDI output not connected."));
+        };
+
+        conn.send(message)
+    }
+
+    /// List raw MIDI devices as outputs in addition to the ports of the backend
+    ///
+    /// Takes effect with the next scan.
+    pub fn set_raw_outputs(&mut self, enabled: bool) {
+        self.raw_outputs = enabled;
+    }
+
+    /// Set the addresses of network MIDI peers listed as outputs
+    ///
+    /// Takes effect with the next scan.
+    pub fn set_network_peers(&mut self, peers: Vec<String>) {
+        self.network_peers = peers;
+    }
+
+    /// Return the ports usable as outputs
+    pub fn get_outputs(&self) -> &[Port] {
+        &self.outputs_list
+    }
+
+    /// Return the ports usable as inputs
+    pub fn get_inputs(&self) -> &[Port] {
+        &self.inputs_list
+    }
+
+    /// Return the output matching a key or name
+    pub fn find_output(&self, key: &str) -> Option<&Port> {
+        find_port(&self.outputs_list, key)
+    }
+
+    /// Return the input matching a key or name
+    pub fn find_input(&self, key: &str) -> Option<&Port> {
+        find_port(&self.inputs_list, key)
+    }
+
+    /// Return the key of the input with the name most similar to an output
+    pub fn paired_input(&self, output_key: &str) -> Option<String> {
+        let output_name = self
+            .find_output(output_key)
+            .map_or(output_key, |port| stable_name(&port.name));
+        let output_key = pairing_key(output_name);
+
+        self.inputs_list
+            .iter()
+            .map(|input| {
+                let matching = output_key
+                    .chars()
+                    .zip(pairing_key(stable_name(&input.name)).chars())
+                    .take_while(|(output, input)| output == input)
+                    .count();
+                (matching, input)
+            })
+            .filter(|(matching, _)| *matching >= PAIRING_MIN_LENGTH)
+            .max_by_key(|(matching, _)| *matching)
+            .map(|(_, input)| input.key.clone())
+    }
+
+    /// Select the output by key or name
+    ///
+    /// Network sessions are not started here, as the invitation takes a while.
+    /// The port is returned by `connecting_output()` instead and the session
+    /// is passed to `session_started()` later.
+    pub fn select_output(&mut self, key: &str) -> Result<()> {
+        if self.output.is_some() {
+            self.output = None;
+            self.output_port = None;
+        }
+        self.connecting = None;
+
+        self.create_scan_clients();
+
+        // Ports may not have been scanned yet
+        if find_port(&self.outputs_list, key).is_none() {
+            self.scan_ports();
+        }
+
+        let Some(port) = find_port(&self.outputs_list, key).cloned() else {
+            return Ok(());
+        };
+
+        if rawmidi::is_raw_id(&port.id) {
+            let connection = rawmidi::Output::open(&port.id)?;
+            log::info!("Raw MIDI output connected to device {}", port.id);
+            self.output = Some(Output::Raw(connection));
+            self.output_port = Some(port);
+            return Ok(());
+        }
+
+        if rtpmidi::is_rtp_id(&port.id) {
+            self.connecting = Some(port);
+            return Ok(());
+        }
+
+        if self.backend == Backend::Jack {
+            let Some(client) = self.jack_client() else {
+                return Err(anyhow!("JACK server not available."));
+            };
+            let connection = client.connect_output(&port.id)?;
+            log::info!("JACK output connected to port {}", port.name);
+            self.output = Some(Output::Jack(connection));
+            self.output_port = Some(port);
+            return Ok(());
         }
 
+        let Some(output_port) = self
+            .scan_output
+            .as_ref()
+            .and_then(|output| output.find_port_by_id(&port.id))
+        else {
+            return Ok(());
+        };
+
+        log::info!("MIDI output connected to port {} ({})", port.name, port.id);
+        let connection = self
+            .scan_output
+            .take()
+            .unwrap()
+            .connect(&output_port, "SysEx Drop Output");
+        if let Ok(connection) = connection {
+            self.output = Some(Output::Midir(connection));
+            self.output_port = Some(port);
+        } else {
+            return Err(anyhow!("MIDI connection error."));
+        }
+
+        Ok(())
+    }
+
+    /// Return the network port whose session is being started
+    pub fn connecting_output(&self) -> Option<&Port> {
+        self.connecting.as_ref()
+    }
+
+    /// Use a started network session as output
+    ///
+    /// The session is discarded if another output was selected in the meantime.
+    pub fn session_started(
+        &mut self,
+        port: &Port,
+        session: Result<rtpmidi::Session>,
+    ) -> Result<()> {
+        if self.connecting.as_ref() != Some(port) {
+            return Ok(());
+        }
+        self.connecting = None;
+
+        let session = session?;
+        log::info!("Network MIDI session with {} started", port.name);
+        self.output = Some(Output::Rtp(session));
+        self.output_port = Some(port.clone());
+
         Ok(())
     }
 
+    /// Return the connected output
+    pub fn output_port(&self) -> Option<&Port> {
+        self.output.as_ref().and(self.output_port.as_ref())
+    }
+
     /// Return the name of the selected output
-    #[allow(dead_code)]
     pub fn output_name(&self) -> Option<String> {
-        self.output.as_ref().map(|_| self.output_name.clone())
+        self.output_port().map(|port| port.name.clone())
+    }
+
+    /// Select the input by key or name
+    pub fn select_input(&mut self, key: &str) -> Result<()> {
+        if self.input.is_some() {
+            self.input = None;
+            self.input_port = None;
+        }
+
+        self.create_scan_clients();
+
+        // Ports may not have been scanned yet
+        if find_port(&self.inputs_list, key).is_none() {
+            self.scan_ports();
+        }
+
+        let Some(port) = find_port(&self.inputs_list, key).cloned() else {
+            return Ok(());
+        };
+
+        if self.backend == Backend::Jack {
+            let Some(client) = self.jack_client() else {
+                return Err(anyhow!("JACK server not available."));
+            };
+            let connection = client.connect_input(&port.id)?;
+            log::info!("JACK input connected to port {}", port.name);
+            self.input = Some(Input::Jack {
+                _connection: connection,
+            });
+            self.input_port = Some(port);
+            return Ok(());
+        }
+
+        let Some(input) = self.scan_input.as_ref() else {
+            return Err(anyhow!("MIDI input not available."));
+        };
+        let Some(input_port) = input.find_port_by_id(&port.id) else {
+            return Ok(());
+        };
+
+        log::info!("MIDI input connected to port {} ({})", port.name, port.id);
+        let subscribers = self.input_subscribers.clone();
+        let mut sysex_buffer = Vec::new();
+        let connection = self.scan_input.take().unwrap().connect(
+            &input_port,
+            "SysEx Drop Input",
+            move |timestamp, data, _| {
+                // SysEx messages may be delivered in several chunks
+                let data = match data.first() {
+                    Some(&SYSEX_START_BYTE) => {
+                        sysex_buffer = data.to_vec();
+                        None
+                    }
+                    Some(byte)
+                        if !sysex_buffer.is_empty()
+                            && (*byte < 0x80 || *byte == SYSEX_END_BYTE) =>
+                    {
+                        sysex_buffer.extend_from_slice(data);
+                        None
+                    }
+                    _ => Some(data.to_vec()),
+                };
+                let data = data.or_else(|| {
+                    (sysex_buffer.last() == Some(&SYSEX_END_BYTE))
+                        .then(|| std::mem::take(&mut sysex_buffer))
+                });
+                if let Some(data) = data {
+                    let message = InputMessage { timestamp, data };
+                    subscribers
+                        .lock()
+                        .unwrap()
+                        .retain(|sender| sender.send(message.clone()).is_ok());
+                }
+            },
+            (),
+        );
+        if let Ok(connection) = connection {
+            self.input = Some(Input::Midir {
+                _connection: connection,
+            });
+            self.input_port = Some(port);
+        } else {
+            return Err(anyhow!("MIDI input connection error."));
+        }
+
+        Ok(())
+    }
+
+    /// Return the connected input
+    pub fn input_port(&self) -> Option<&Port> {
+        self.input.as_ref().and(self.input_port.as_ref())
+    }
+
+    /// Return the name of the selected input
+    pub fn input_name(&self) -> Option<String> {
+        self.input_port().map(|port| port.name.clone())
+    }
+
+    /// Return a receiver for all messages arriving on the input from now on
+    pub fn subscribe_input(&self) -> Receiver<InputMessage> {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.input_subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_byte_found() {
+        assert_eq!(
+            find_status_byte(&[0xF0, 0x41, 0x90, 0x01, 0xF7]),
+            Some((2, 0x90))
+        );
+        assert_eq!(find_status_byte(&[0xF0, 0x41, 0xF6, 0xF7]), Some((2, 0xF6)));
+        // Packet without end byte
+        assert_eq!(find_status_byte(&[0xF0, 0x41, 0x80]), Some((2, 0x80)));
+    }
+
+    #[test]
+    fn status_byte_ignores_realtime() {
+        assert_eq!(
+            find_status_byte(&[0xF0, 0x41, 0xF8, 0x01, 0xFE, 0xF7]),
+            None
+        );
+        assert_eq!(find_status_byte(&[0xF0, 0x41, 0x7F, 0xF7]), None);
+    }
+
+    /// Return a port list from identifiers and names
+    fn ports(ports: &[(&str, &str)]) -> Vec<Port> {
+        port_list(
+            ports
+                .iter()
+                .map(|(id, name)| (id.to_string(), name.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn stable_name_strips_alsa_numbers() {
+        assert_eq!(stable_name("XYZ:XYZ MIDI 1 24:0"), "XYZ:XYZ MIDI 1");
+        assert_eq!(stable_name("XYZ:XYZ MIDI 1 128:12"), "XYZ:XYZ MIDI 1");
+    }
+
+    #[test]
+    fn stable_name_keeps_other_names() {
+        assert_eq!(stable_name("XYZ MIDI 1"), "XYZ MIDI 1");
+        assert_eq!(stable_name("XYZ 24:"), "XYZ 24:");
+        assert_eq!(stable_name("XYZ a:0"), "XYZ a:0");
+        assert_eq!(stable_name("24:0"), "24:0");
+    }
+
+    #[test]
+    fn port_list_unique_names() {
+        let list = ports(&[("1", "ABC 20:0"), ("2", "XYZ 24:0")]);
+        assert_eq!(list[0].label, "ABC 20:0");
+        assert_eq!(list[0].key, "ABC");
+        assert_eq!(list[1].label, "XYZ 24:0");
+        assert_eq!(list[1].key, "XYZ");
+    }
+
+    #[test]
+    fn port_list_numbers_duplicates() {
+        let list = ports(&[("1", "XYZ"), ("2", "ABC"), ("3", "XYZ"), ("4", "XYZ")]);
+        assert_eq!(list[0].label, "XYZ #1");
+        assert_eq!(list[0].key, "XYZ");
+        assert_eq!(list[1].label, "ABC");
+        assert_eq!(list[2].label, "XYZ #2");
+        assert_eq!(list[2].key, "XYZ #2");
+        assert_eq!(list[3].label, "XYZ #3");
+        assert_eq!(list[3].key, "XYZ #3");
+    }
+
+    #[test]
+    fn port_list_duplicates_with_alsa_numbers() {
+        let list = ports(&[("1", "XYZ 24:0"), ("2", "XYZ 28:0")]);
+        assert_eq!(list[0].key, "XYZ");
+        assert_eq!(list[1].key, "XYZ #2");
+    }
+
+    #[test]
+    fn find_port_by_key_and_name() {
+        let list = ports(&[("1", "XYZ 24:0"), ("2", "XYZ 28:0"), ("3", "ABC")]);
+        assert_eq!(
+            find_port(&list, "XYZ #2").map(|port| &port.id[..]),
+            Some("2")
+        );
+        assert_eq!(
+            find_port(&list, "XYZ 28:0").map(|port| &port.id[..]),
+            Some("2")
+        );
+        assert_eq!(find_port(&list, "ABC").map(|port| &port.id[..]), Some("3"));
+        assert!(find_port(&list, "DEF").is_none());
+    }
+
+    #[test]
+    fn find_port_with_outdated_numbers() {
+        let list = ports(&[("1", "XYZ 30:0")]);
+        assert_eq!(
+            find_port(&list, "XYZ 24:0").map(|port| &port.id[..]),
+            Some("1")
+        );
     }
 }
@@ -0,0 +1,261 @@
+//! Module containing the monitor window for incoming MIDI messages
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+use eframe::egui;
+
+use crate::{Message, midi};
+
+/// Size of the monitor window
+const WINDOW_SIZE: egui::Vec2 = egui::vec2(560.0, 400.0);
+
+/// Maximum number of messages kept in the window
+const MESSAGE_LIMIT: usize = 5000;
+
+/// Monitor window state
+#[derive(Default)]
+pub struct MonitorWindow {
+    /// Window is shown
+    pub open: bool,
+
+    /// Receiver for the input messages while the window is open
+    receiver: Option<Receiver<midi::InputMessage>>,
+
+    /// Received messages, oldest first
+    messages: VecDeque<midi::InputMessage>,
+
+    /// New messages are discarded while paused
+    paused: bool,
+
+    /// Hide system real-time messages like clock and active sensing, only affects the display
+    hide_realtime: bool,
+
+    /// Result of the last save action
+    status: Option<String>,
+}
+
+/// Return a readable description of a message
+fn describe(data: &[u8]) -> String {
+    let Some(&status) = data.first() else {
+        return String::new();
+    };
+
+    let channel = (status & 0x0F) + 1;
+    let data1 = data.get(1).copied().unwrap_or_default();
+    let data2 = data.get(2).copied().unwrap_or_default();
+
+    match status {
+        0x80..=0x8F => format!("Ch {:<2} Note Off       {} vel {}", channel, data1, data2),
+        0x90..=0x9F => format!("Ch {:<2} Note On        {} vel {}", channel, data1, data2),
+        0xA0..=0xAF => format!("Ch {:<2} Poly Pressure  {} {}", channel, data1, data2),
+        0xB0..=0xBF => format!("Ch {:<2} Control Change {} = {}", channel, data1, data2),
+        0xC0..=0xCF => format!("Ch {:<2} Program Change {}", channel, data1),
+        0xD0..=0xDF => format!("Ch {:<2} Channel Pressure {}", channel, data1),
+        0xE0..=0xEF => {
+            let value = ((data2 as i32) << 7 | data1 as i32) - 8192;
+            format!("Ch {:<2} Pitch Bend     {}", channel, value)
+        }
+        midi::SYSEX_START_BYTE => {
+            format!("SysEx ({} bytes) {}", data.len(), midi::format_hex(data))
+        }
+        0xF1 => format!("MTC Quarter Frame {}", data1),
+        0xF2 => format!("Song Position {}", (data2 as u16) << 7 | data1 as u16),
+        0xF3 => format!("Song Select {}", data1),
+        0xF6 => "Tune Request".to_string(),
+        0xF8 => "Clock".to_string(),
+        0xFA => "Start".to_string(),
+        0xFB => "Continue".to_string(),
+        0xFC => "Stop".to_string(),
+        0xFE => "Active Sensing".to_string(),
+        0xFF => "Reset".to_string(),
+        _ => midi::format_hex(data),
+    }
+}
+
+/// Return if the message is a system real-time message
+fn is_realtime(data: &[u8]) -> bool {
+    data.len() == 1 && data[0] >= 0xF8
+}
+
+/// Format a timestamp in microseconds as seconds with millisecond resolution
+fn format_timestamp(timestamp: u64) -> String {
+    format!("{:.3}", timestamp as f64 / 1_000_000.0)
+}
+
+impl MonitorWindow {
+    /// Show the window if open
+    ///
+    /// The input can't be changed while a transfer is running.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        midi: &Arc<Mutex<midi::MidiConnector>>,
+        selected_input: Option<String>,
+        running: bool,
+        message_sender: &Sender<Message>,
+    ) {
+        if !self.open {
+            // Unsubscribes from the input
+            self.receiver = None;
+            return;
+        }
+
+        let receiver = self
+            .receiver
+            .get_or_insert_with(|| midi.lock().unwrap().subscribe_input());
+        for message in receiver.try_iter() {
+            if self.paused {
+                continue;
+            }
+            self.messages.push_back(message);
+            if self.messages.len() > MESSAGE_LIMIT {
+                self.messages.pop_front();
+            }
+        }
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("monitor_window"),
+            egui::ViewportBuilder::default()
+                .with_title("MIDI Monitor")
+                .with_inner_size(WINDOW_SIZE),
+            |ui, _class| {
+                if ui.ctx().input(|i| i.viewport().close_requested()) {
+                    self.open = false;
+                }
+
+                egui::CentralPanel::default().show(ui, |ui| {
+                    ui.scope(|ui| {
+                        if running {
+                            ui.disable();
+                        }
+
+                        let connected = midi.lock().unwrap().input_name().is_some();
+                        crate::device_selection(
+                            ui,
+                            "Input:",
+                            midi.lock().unwrap().get_inputs(),
+                            selected_input.clone(),
+                            connected,
+                            message_sender,
+                            Message::OverrideInput,
+                        );
+                    });
+                    self.ui(ui);
+                });
+            },
+        );
+    }
+
+    /// Contents of the monitor window
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.paused, "Pause")
+                .on_hover_text("Discard incoming messages");
+            ui.checkbox(&mut self.hide_realtime, "Hide real-time")
+                .on_hover_text("Hide clock, active sensing and other real-time messages");
+            if ui.button("Clear").clicked() {
+                self.messages.clear();
+                self.status = None;
+            }
+            if ui
+                .add_enabled(!self.messages.is_empty(), egui::Button::new("Save log…"))
+                .on_hover_text("Save the shown messages as text file")
+                .clicked()
+            {
+                self.save_log();
+            }
+            if ui
+                .add_enabled(
+                    self.messages
+                        .iter()
+                        .any(|message| message.data.first() == Some(&midi::SYSEX_START_BYTE)),
+                    egui::Button::new("Save SysEx…"),
+                )
+                .on_hover_text("Save all SysEx messages as .syx file")
+                .clicked()
+            {
+                self.save_sysex();
+            }
+        });
+
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+
+        ui.separator();
+
+        // Only the rows in view are laid out
+        let messages: Vec<&midi::InputMessage> = self.visible_messages().collect();
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both()
+            .stick_to_bottom(true)
+            .auto_shrink(false)
+            .show_rows(ui, row_height, messages.len(), |ui, rows| {
+                for message in &messages[rows] {
+                    ui.monospace(format!(
+                        "{:>12}  {}",
+                        format_timestamp(message.timestamp),
+                        describe(&message.data)
+                    ));
+                }
+            });
+    }
+
+    /// Return the messages not hidden by the filter
+    fn visible_messages(&self) -> impl Iterator<Item = &midi::InputMessage> {
+        self.messages
+            .iter()
+            .filter(|message| !(self.hide_realtime && is_realtime(&message.data)))
+    }
+
+    /// Save the messages as text file chosen by the user
+    fn save_log(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Text", &["txt"])
+            .set_file_name("midi-monitor.txt")
+            .save_file()
+        else {
+            return;
+        };
+
+        let text: String = self
+            .visible_messages()
+            .map(|message| {
+                format!(
+                    "{}\t{}\n",
+                    format_timestamp(message.timestamp),
+                    describe(&message.data)
+                )
+            })
+            .collect();
+
+        self.set_save_status(std::fs::write(&path, text), &path);
+    }
+
+    /// Save the SysEx messages to a file chosen by the user
+    fn save_sysex(&mut self) {
+        let Some(path) = crate::save_file_dialog(&crate::FileType::SysEx, None, "midi-monitor.syx")
+        else {
+            return;
+        };
+
+        let data: Vec<u8> = self
+            .messages
+            .iter()
+            .filter(|message| message.data.first() == Some(&midi::SYSEX_START_BYTE))
+            .flat_map(|message| message.data.iter().copied())
+            .collect();
+
+        self.set_save_status(std::fs::write(&path, data), &path);
+    }
+
+    /// Set the status according to the result of saving a file
+    fn set_save_status(&mut self, result: std::io::Result<()>, path: &std::path::Path) {
+        self.status = Some(match result {
+            Ok(()) => format!("Saved to {}.", path.display()),
+            Err(error) => format!("Error: {}", error),
+        });
+    }
+}
@@ -0,0 +1,169 @@
+//! Module containing the pacing of transfers by replies of the device
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::midi;
+
+/// Interval for checking the cancel request while waiting
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Settings for waiting on a reply after each packet
+#[derive(Debug, Clone)]
+pub struct ReplyPacing {
+    /// Expected start of the reply, `None` matches any byte
+    pub pattern: Vec<Option<u8>>,
+
+    /// Time to wait for the reply
+    pub timeout: Duration,
+
+    /// Number of times a packet is sent again if no reply arrives
+    pub retries: u32,
+}
+
+/// Result of waiting for a reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    /// Matching reply received
+    Received,
+
+    /// No matching reply within the timeout
+    Timeout,
+
+    /// Cancelled via the receiver
+    Cancelled,
+}
+
+/// Parse a reply pattern in hex notation like `F0 00 20 3C XX 7F`
+///
+/// `XX` or `??` match any byte. An empty pattern matches every SysEx message.
+pub fn parse_pattern(text: &str) -> Option<Vec<Option<u8>>> {
+    let digits: String = text.split_whitespace().collect();
+
+    // Wildcards are parsed as zero bytes first
+    let bytes = midi::parse_hex(
+        &digits
+            .replace("XX", "00")
+            .replace("xx", "00")
+            .replace("??", "00"),
+    )?;
+
+    if bytes.is_empty() {
+        return Some(vec![Some(midi::SYSEX_START_BYTE)]);
+    }
+
+    bytes
+        .into_iter()
+        .enumerate()
+        .map(|(index, byte)| match &digits[index * 2..index * 2 + 2] {
+            "XX" | "xx" | "??" => Some(None),
+            // Wildcard not aligned to a byte
+            pair if pair.contains(['X', 'x', '?']) => None,
+            _ => Some(Some(byte)),
+        })
+        .collect()
+}
+
+impl ReplyPacing {
+    /// Return if a message matches the pattern
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.pattern.len()
+            && self
+                .pattern
+                .iter()
+                .zip(data)
+                .all(|(expected, byte)| expected.is_none_or(|expected| expected == *byte))
+    }
+
+    /// Wait for a matching reply on the input
+    pub fn wait(
+        &self,
+        input_receiver: &Receiver<midi::InputMessage>,
+        cancel_receiver: &Receiver<bool>,
+    ) -> Reply {
+        let deadline = Instant::now() + self.timeout;
+
+        while Instant::now() < deadline {
+            if cancel_receiver.try_recv().is_ok() {
+                return Reply::Cancelled;
+            }
+
+            match input_receiver.recv_timeout(POLL_INTERVAL) {
+                Ok(message) if self.matches(&message.data) => return Reply::Received,
+                Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        Reply::Timeout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return pacing settings with the pattern
+    fn pacing(pattern: &str) -> ReplyPacing {
+        ReplyPacing {
+            pattern: parse_pattern(pattern).unwrap(),
+            timeout: Duration::from_millis(100),
+            retries: 0,
+        }
+    }
+
+    #[test]
+    fn parse_bytes() {
+        assert_eq!(
+            parse_pattern("F0 00 20 3c"),
+            Some(vec![Some(0xF0), Some(0x00), Some(0x20), Some(0x3C)])
+        );
+        assert_eq!(
+            parse_pattern("F00020"),
+            Some(vec![Some(0xF0), Some(0x00), Some(0x20)])
+        );
+    }
+
+    #[test]
+    fn parse_wildcards() {
+        assert_eq!(
+            parse_pattern("F0 XX xx ?? 7F"),
+            Some(vec![Some(0xF0), None, None, None, Some(0x7F)])
+        );
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(parse_pattern(""), Some(vec![Some(midi::SYSEX_START_BYTE)]));
+        assert_eq!(
+            parse_pattern("  "),
+            Some(vec![Some(midi::SYSEX_START_BYTE)])
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse_pattern("F0 0"), None);
+        assert_eq!(parse_pattern("F0 GG"), None);
+        assert_eq!(parse_pattern("F0 X1"), None);
+        assert_eq!(parse_pattern("F0 ÄÄ"), None);
+        assert_eq!(parse_pattern("F0 0X X0"), None);
+    }
+
+    #[test]
+    fn matches_wildcard() {
+        let pacing = pacing("F0 41 XX 7F");
+        assert!(pacing.matches(&[0xF0, 0x41, 0x10, 0x7F, 0xF7]));
+        assert!(pacing.matches(&[0xF0, 0x41, 0x00, 0x7F]));
+        assert!(!pacing.matches(&[0xF0, 0x42, 0x10, 0x7F, 0xF7]));
+        assert!(!pacing.matches(&[0xF0, 0x41, 0x10]));
+    }
+
+    #[test]
+    fn matches_any_sysex() {
+        let pacing = pacing("");
+        assert!(pacing.matches(&[0xF0, 0x7E, 0xF7]));
+        assert!(!pacing.matches(&[0xF8]));
+        assert!(!pacing.matches(&[]));
+    }
+}
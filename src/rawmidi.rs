@@ -0,0 +1,162 @@
+//! Module containing the ALSA raw MIDI output
+//!
+//! Raw MIDI devices like `hw:1,0,0` are written directly, bypassing the
+//! sequencer and its buffering. Only available on Linux.
+//!
+//! Devices are opened non-blocking. Writing returns as soon as the driver
+//! buffer is full, so callers can release locks while the device catches up.
+
+use anyhow::Result;
+
+/// Interval for polling the driver buffer while it is full or not yet empty
+pub const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(1);
+
+/// Prefix of the identifiers of raw MIDI ports
+const ID_PREFIX: &str = "hw:";
+
+/// Return if a port identifier belongs to a raw MIDI port
+pub fn is_raw_id(id: &str) -> bool {
+    id.starts_with(ID_PREFIX)
+}
+
+/// Return identifiers and names of the raw MIDI outputs
+#[cfg(target_os = "linux")]
+pub fn outputs() -> Vec<(String, String)> {
+    let mut outputs = Vec::new();
+
+    for card in alsa::card::Iter::new().filter_map(|card| card.ok()) {
+        let Ok(ctl) = alsa::Ctl::from_card(&card, false) else {
+            continue;
+        };
+        let card_name = card.get_name().unwrap_or_default();
+
+        for info in alsa::rawmidi::Iter::new(&ctl).filter_map(|info| info.ok()) {
+            if info.get_stream() != alsa::Direction::Playback {
+                continue;
+            }
+            let id = format!(
+                "{}{},{},{}",
+                ID_PREFIX,
+                card.get_index(),
+                info.get_device(),
+                info.get_subdevice()
+            );
+            let subdevice_name = info.get_subdevice_name().unwrap_or_default();
+            outputs.push((
+                id.clone(),
+                format!("{}:{} (raw {})", card_name, subdevice_name, id),
+            ));
+        }
+    }
+
+    outputs
+}
+
+/// Return identifiers and names of the raw MIDI outputs, none on this platform
+#[cfg(not(target_os = "linux"))]
+pub fn outputs() -> Vec<(String, String)> {
+    Vec::new()
+}
+
+/// Connection to a raw MIDI output
+#[cfg(target_os = "linux")]
+pub struct Output {
+    /// Device opened in non-blocking mode
+    rawmidi: alsa::rawmidi::Rawmidi,
+
+    /// Size of the driver buffer, available space when it is empty
+    buffer_size: usize,
+}
+
+#[cfg(target_os = "linux")]
+impl Output {
+    /// Open the device with the identifier
+    pub fn open(id: &str) -> Result<Self> {
+        let rawmidi = alsa::rawmidi::Rawmidi::new(id, alsa::Direction::Playback, true)
+            .map_err(|error| anyhow::anyhow!("Raw MIDI connection error: {}", error))?;
+        let buffer_size = rawmidi
+            .status()
+            .map_err(|error| anyhow::anyhow!("Raw MIDI connection error: {}", error))?
+            .get_avail();
+        Ok(Self {
+            rawmidi,
+            buffer_size,
+        })
+    }
+
+    /// Write as much of the data as fits into the driver buffer, returns the number of bytes
+    pub fn write(&mut self, data: &[u8]) -> Result<usize> {
+        use std::io::Write;
+
+        match self.rawmidi.io().write(data) {
+            Ok(count) => Ok(count),
+            Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => Ok(0),
+            Err(error) => Err(anyhow::anyhow!("Raw MIDI send error: {}", error)),
+        }
+    }
+
+    /// Return if all written data was passed on by the driver
+    ///
+    /// Waiting for this keeps the delay between packets independent of the
+    /// buffer size of the driver.
+    pub fn is_drained(&self) -> Result<bool> {
+        let status = self
+            .rawmidi
+            .status()
+            .map_err(|error| anyhow::anyhow!("Raw MIDI send error: {}", error))?;
+        Ok(status.get_avail() >= self.buffer_size)
+    }
+
+    /// Write a message and wait until it is transmitted
+    pub fn send(&mut self, message: &[u8]) -> Result<()> {
+        let mut written = 0;
+
+        while written < message.len() {
+            written += self.write(&message[written..])?;
+            if written < message.len() {
+                std::thread::sleep(POLL_INTERVAL);
+            }
+        }
+
+        while !self.is_drained()? {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        Ok(())
+    }
+}
+
+/// Connection to a raw MIDI output, not available on this platform
+#[cfg(not(target_os = "linux"))]
+pub struct Output;
+
+#[cfg(not(target_os = "linux"))]
+impl Output {
+    /// Open the device with the identifier
+    pub fn open(_id: &str) -> Result<Self> {
+        Err(anyhow::anyhow!(
+            "Raw MIDI is not available on this platform."
+        ))
+    }
+
+    /// Write as much of the data as fits into the driver buffer, returns the number of bytes
+    pub fn write(&mut self, _data: &[u8]) -> Result<usize> {
+        Err(anyhow::anyhow!(
+            "Raw MIDI is not available on this platform."
+        ))
+    }
+
+    /// Return if all written data was passed on by the driver
+    pub fn is_drained(&self) -> Result<bool> {
+        Err(anyhow::anyhow!(
+            "Raw MIDI is not available on this platform."
+        ))
+    }
+
+    /// Write a message and wait until it is transmitted
+    pub fn send(&mut self, _message: &[u8]) -> Result<()> {
+        Err(anyhow::anyhow!(
+            "Raw MIDI is not available on this platform."
+        ))
+    }
+}
@@ -0,0 +1,273 @@
+//! Module containing the remote control server
+//!
+//! A small HTTP API on the loopback interface for controlling transfers from
+//! other applications. Requests are mapped onto messages for the application,
+//! the state is reported from a snapshot updated by the application.
+//!
+//! Endpoints:
+//!
+//! - `GET /status`: transfer state, progress, file, device, connection and error message.
+//! - `GET /ports`: MIDI outputs and inputs with key, name, label and backend ID.
+//! - `POST /device`: select the output with the key or name in `{"device": ...}`,
+//!   refused during a transfer.
+//! - `POST /load`: load the file with the path in `{"path": ...}`.
+//! - `POST /start`: start the transfer of the loaded file, refused without a
+//!   file or during a transfer.
+//! - `POST /cancel`: cancel the running transfer.
+//!
+//! Requests must address the server as `127.0.0.1` or `localhost` and must not
+//! carry an `Origin` header, POST requests need `Content-Type: application/json`.
+//! Browsers can't send such requests from other sites without a preflight the
+//! server doesn't answer, which protects against DNS rebinding and forged
+//! requests from web pages.
+
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Result, anyhow};
+
+use crate::{Message, TransferState, midi};
+
+/// Default TCP port of the server
+pub const DEFAULT_PORT: u16 = 7890;
+
+/// Snapshot of the application state reported by the server
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Status {
+    /// State of the transfer
+    pub state: TransferState,
+
+    /// Transfer progress from 0.0 to 1.0
+    pub progress: f32,
+
+    /// Path of the loaded file
+    pub file: Option<std::path::PathBuf>,
+
+    /// Key of the selected output
+    pub device: Option<String>,
+
+    /// Selected output is connected
+    pub connected: bool,
+
+    /// Current error message
+    pub error: Option<String>,
+}
+
+/// Running server, stopped when dropped
+pub struct Server {
+    /// TCP port the server listens on
+    port: u16,
+
+    /// HTTP server shared with the request thread
+    http: Arc<tiny_http::Server>,
+
+    /// State reported on status requests
+    status: Arc<Mutex<Option<Status>>>,
+}
+
+impl Server {
+    /// Start the server on the loopback interface
+    pub fn start(
+        port: u16,
+        midi: Arc<Mutex<midi::MidiConnector>>,
+        message_sender: Sender<Message>,
+    ) -> Result<Self> {
+        let http =
+            Arc::new(tiny_http::Server::http(("127.0.0.1", port)).map_err(|error| anyhow!(error))?);
+        let status = Arc::new(Mutex::new(None));
+
+        let thread_http = http.clone();
+        let thread_status = status.clone();
+        std::thread::spawn(move || {
+            for mut request in thread_http.incoming_requests() {
+                let (code, body) =
+                    handle_request(&mut request, port, &midi, &message_sender, &thread_status);
+                let header = tiny_http::Header::from_bytes("Content-Type", "application/json")
+                    .expect("valid header");
+                let response = tiny_http::Response::from_string(body.to_string())
+                    .with_status_code(code)
+                    .with_header(header);
+                request.respond(response).ok();
+            }
+        });
+
+        log::info!("Remote control listening on 127.0.0.1:{}.", port);
+
+        Ok(Self { port, http, status })
+    }
+
+    /// Return the TCP port the server listens on
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Update the state reported by the server
+    pub fn set_status(&self, status: Status) {
+        *self.status.lock().unwrap() = Some(status);
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.http.unblock();
+    }
+}
+
+/// Body of the POST requests with parameters
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct Parameters {
+    /// Key or name of the output device
+    device: String,
+
+    /// Path of the file to load
+    path: std::path::PathBuf,
+}
+
+/// Return the value of a request header
+fn header<'a>(request: &'a tiny_http::Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+/// Return if the `Host` header addresses the server on the loopback interface
+fn is_local_host(host: &str, port: u16) -> bool {
+    host == format!("127.0.0.1:{}", port) || host == format!("localhost:{}", port)
+}
+
+/// Return if the `Content-Type` header denotes JSON
+fn is_json(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case("application/json"))
+}
+
+/// Return if the reported state is a running transfer
+fn running(status: &Mutex<Option<Status>>) -> bool {
+    status
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|status| status.state == TransferState::Running)
+}
+
+/// Process a request, returns the status code and JSON body of the response
+fn handle_request(
+    request: &mut tiny_http::Request,
+    port: u16,
+    midi: &Arc<Mutex<midi::MidiConnector>>,
+    message_sender: &Sender<Message>,
+    status: &Arc<Mutex<Option<Status>>>,
+) -> (u16, serde_json::Value) {
+    if !header(request, "Host").is_some_and(|host| is_local_host(host, port))
+        || header(request, "Origin").is_some()
+    {
+        return (403, serde_json::json!({ "error": "Request not allowed." }));
+    }
+
+    let mut parameters = Parameters::default();
+    if *request.method() == tiny_http::Method::Post {
+        if !header(request, "Content-Type").is_some_and(is_json) {
+            return (
+                415,
+                serde_json::json!({ "error": "Content type must be application/json." }),
+            );
+        }
+        let mut body = String::new();
+        if request.as_reader().read_to_string(&mut body).is_err() {
+            return (400, serde_json::json!({ "error": "Invalid request body." }));
+        }
+        if !body.trim().is_empty() {
+            match serde_json::from_str(&body) {
+                Ok(body) => parameters = body,
+                Err(_) => {
+                    return (400, serde_json::json!({ "error": "Invalid request body." }));
+                }
+            }
+        }
+    }
+
+    let message = match (request.method(), request.url()) {
+        (tiny_http::Method::Get, "/status") => {
+            return (200, serde_json::json!(*status.lock().unwrap()));
+        }
+        (tiny_http::Method::Get, "/ports") => {
+            let midi = midi.lock().unwrap();
+            return (
+                200,
+                serde_json::json!({
+                    "outputs": midi.get_outputs(),
+                    "inputs": midi.get_inputs(),
+                }),
+            );
+        }
+        (tiny_http::Method::Post, "/device") => {
+            if running(status) {
+                return (409, serde_json::json!({ "error": "Transfer running." }));
+            }
+            if midi
+                .lock()
+                .unwrap()
+                .find_output(&parameters.device)
+                .is_none()
+            {
+                return (404, serde_json::json!({ "error": "Unknown device." }));
+            }
+            Message::SelectDevice(parameters.device)
+        }
+        (tiny_http::Method::Post, "/load") => Message::LoadFile(parameters.path),
+        (tiny_http::Method::Post, "/start") => {
+            if running(status) {
+                return (409, serde_json::json!({ "error": "Transfer running." }));
+            }
+            if status
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_none_or(|status| status.file.is_none())
+            {
+                return (409, serde_json::json!({ "error": "No file loaded." }));
+            }
+            Message::StartTransfer
+        }
+        (tiny_http::Method::Post, "/cancel") => Message::CancelTransfer,
+        _ => return (404, serde_json::json!({ "error": "Unknown endpoint." })),
+    };
+
+    message_sender.send(message).ok();
+
+    (202, serde_json::json!({ "accepted": true }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_host() {
+        assert!(is_local_host("127.0.0.1:7890", 7890));
+        assert!(is_local_host("localhost:7890", 7890));
+        assert!(!is_local_host("127.0.0.1:7891", 7890));
+        assert!(!is_local_host("127.0.0.1", 7890));
+        assert!(!is_local_host("attacker.example:7890", 7890));
+    }
+
+    #[test]
+    fn json_content_type() {
+        assert!(is_json("application/json"));
+        assert!(is_json("Application/JSON; charset=utf-8"));
+        assert!(!is_json("text/plain"));
+        assert!(!is_json("application/x-www-form-urlencoded"));
+    }
+
+    #[test]
+    fn parameters() {
+        let parameters: Parameters = serde_json::from_str(r#"{"path": "/tmp/dump.syx"}"#).unwrap();
+        assert_eq!(parameters.path, std::path::PathBuf::from("/tmp/dump.syx"));
+        assert!(parameters.device.is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+//! Module containing the machine-readable reporting mode
+//!
+//! When enabled via the `--json` argument, validation results, transfer events
+//! and log messages are written to stdout as line-delimited JSON.
+//!
+//! On Windows, the application is built for the GUI subsystem and has no
+//! console attached, so nothing is written there.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::checksum::{KAWAI_ID, KORG_ID, ROLAND_ID, YAMAHA_ID};
+
+/// JSON mode enabled flag
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Event written as a single JSON line
+#[derive(Debug, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// File was loaded successfully
+    FileValidated {
+        /// Path of the file
+        path: std::path::PathBuf,
+
+        /// Info for each packet
+        packets: Vec<PacketInfo>,
+
+        /// Warnings about the contents
+        warnings: Vec<String>,
+    },
+
+    /// File was rejected
+    FileRejected {
+        /// Path of the file
+        path: std::path::PathBuf,
+
+        /// Reason for rejecting the file
+        error: String,
+    },
+
+    /// Packet was sent
+    PacketTransferred {
+        /// Number of packets sent so far
+        count: usize,
+    },
+
+    /// Transfer finished successfully
+    TransferFinished,
+
+    /// Transfer was cancelled by the user
+    TransferCancelled,
+
+    /// Replies of the device were compared with the sent packets
+    VerificationFinished {
+        /// All packets were verified successfully
+        all_match: bool,
+
+        /// Result for each packet
+        results: Vec<String>,
+    },
+
+    /// Error occurred
+    Error {
+        /// Error message
+        message: String,
+    },
+
+    /// Log message
+    Log {
+        /// Log level
+        level: String,
+
+        /// Module that created the message
+        target: String,
+
+        /// Message text
+        message: String,
+    },
+}
+
+/// Info about a single packet
+#[derive(Debug, serde::Serialize)]
+pub struct PacketInfo {
+    /// Size in bytes including start and end byte
+    pub size: usize,
+
+    /// Manufacturer name if known, otherwise the ID in hex notation
+    pub manufacturer: String,
+}
+
+impl PacketInfo {
+    /// Return the info for a packet
+    pub fn new(packet: &[u8]) -> Self {
+        Self {
+            size: packet.len(),
+            manufacturer: manufacturer(packet),
+        }
+    }
+}
+
+/// Enable the JSON mode, replacing the default logger
+pub fn enable() -> Result<(), log::SetLoggerError> {
+    ENABLED.store(true, Ordering::Relaxed);
+    log::set_logger(&JsonLogger)?;
+    log::set_max_level(log::LevelFilter::Debug);
+    Ok(())
+}
+
+/// Return if the JSON mode is enabled
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Write an event if the JSON mode is enabled
+pub fn emit(event: Event) {
+    if !enabled() {
+        return;
+    }
+
+    if let Ok(line) = serde_json::to_string(&event) {
+        // Writing must not panic when stdout is closed
+        let _ = writeln!(std::io::stdout().lock(), "{}", line);
+    }
+}
+
+/// Return the manufacturer of a packet
+fn manufacturer(packet: &[u8]) -> String {
+    match packet.get(1..) {
+        Some([KAWAI_ID, ..]) => "Kawai".to_string(),
+        Some([ROLAND_ID, ..]) => "Roland".to_string(),
+        Some([KORG_ID, ..]) => "Korg".to_string(),
+        Some([YAMAHA_ID, ..]) => "Yamaha".to_string(),
+        Some([0x7E, ..]) => "Universal Non-Real Time".to_string(),
+        Some([0x7F, ..]) => "Universal Real Time".to_string(),
+        // Extended IDs consist of three bytes
+        Some([0x00, id1, id2, ..]) => crate::midi::format_hex(&[0x00, *id1, *id2]),
+        Some([id, ..]) => crate::midi::format_hex(&[*id]),
+        _ => String::new(),
+    }
+}
+
+/// Logger writing the messages as JSON events
+struct JsonLogger;
+
+impl log::Log for JsonLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // Same levels as the default logger
+        if metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            metadata.level() <= log::Level::Debug
+        } else {
+            metadata.level() <= log::Level::Info
+        }
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            emit(Event::Log {
+                level: record.level().to_string(),
+                target: record.target().to_string(),
+                message: record.args().to_string(),
+            });
+        }
+    }
+
+    fn flush(&self) {}
+}
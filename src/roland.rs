@@ -0,0 +1,283 @@
+//! Module containing the decoding and encoding of Roland DT1/RQ1 packets
+
+use crate::checksum::{self, ROLAND_DT1, ROLAND_RQ1};
+use crate::midi::SYSEX_END_BYTE;
+
+/// Roland commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// Data request (RQ1)
+    Request,
+
+    /// Data set (DT1)
+    Set,
+}
+
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Request => "RQ1",
+                Self::Set => "DT1",
+            }
+        )
+    }
+}
+
+/// Decoded Roland packet
+#[derive(Debug, Clone)]
+pub struct Packet {
+    /// Bytes from start byte up to and including the model ID
+    header: Vec<u8>,
+
+    /// Command of the packet
+    pub command: Command,
+
+    /// Address bytes
+    pub address: Vec<u8>,
+
+    /// Data bytes for DT1, size bytes for RQ1
+    pub body: Vec<u8>,
+
+    /// Checksum found in the packet
+    pub checksum: u8,
+}
+
+impl Packet {
+    /// Decode a packet using the given number of address bytes
+    pub fn parse(bytes: &[u8], address_size: usize) -> Option<Self> {
+        let command_offset = checksum::roland_command_offset(bytes)?;
+        let address_offset = command_offset + 1;
+        let body_offset = address_offset + address_size;
+        let checksum_offset = bytes.len().checked_sub(2)?;
+
+        if bytes[bytes.len() - 1] != SYSEX_END_BYTE || checksum_offset <= body_offset {
+            return None;
+        }
+
+        let command = match bytes[command_offset] {
+            ROLAND_RQ1 => Command::Request,
+            ROLAND_DT1 => Command::Set,
+            _ => return None,
+        };
+
+        Some(Self {
+            header: bytes[..command_offset].to_vec(),
+            command,
+            address: bytes[address_offset..body_offset].to_vec(),
+            body: bytes[body_offset..checksum_offset].to_vec(),
+            checksum: bytes[checksum_offset],
+        })
+    }
+
+    /// Encode the packet with a recalculated checksum
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.clone();
+        bytes.push(match self.command {
+            Command::Request => ROLAND_RQ1,
+            Command::Set => ROLAND_DT1,
+        });
+        bytes.extend_from_slice(&self.address);
+        bytes.extend_from_slice(&self.body);
+        bytes.push(self.calculate_checksum());
+        bytes.push(SYSEX_END_BYTE);
+
+        bytes
+    }
+
+    /// Return the checksum for the current address and body
+    pub fn calculate_checksum(&self) -> u8 {
+        let mut bytes = self.address.clone();
+        bytes.extend_from_slice(&self.body);
+        checksum::roland_checksum(&bytes)
+    }
+
+    /// Return if the stored checksum matches the contents
+    pub fn checksum_valid(&self) -> bool {
+        self.checksum == self.calculate_checksum()
+    }
+
+    /// Return the start address as a linear value
+    pub fn start_address(&self) -> u32 {
+        to_linear(&self.address)
+    }
+
+    /// Return the value at an address if it is covered by this DT1 packet
+    pub fn get(&self, address: u32) -> Option<u8> {
+        self.data_index(address).map(|index| self.body[index])
+    }
+
+    /// Set the value at an address, returns `false` if not covered by this DT1 packet
+    pub fn set(&mut self, address: u32, value: u8) -> bool {
+        match self.data_index(address) {
+            Some(index) => {
+                self.body[index] = value & 0x7F;
+                self.checksum = self.calculate_checksum();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return a RQ1 packet requesting the data of this DT1 packet
+    pub fn to_request(&self) -> Option<Self> {
+        if self.command != Command::Set {
+            return None;
+        }
+
+        Some(Self {
+            header: self.header.clone(),
+            command: Command::Request,
+            address: self.address.clone(),
+            body: from_linear(self.body.len() as u32, self.address.len()),
+            checksum: 0,
+        })
+    }
+
+    /// Return the index into the data for an address
+    fn data_index(&self, address: u32) -> Option<usize> {
+        if self.command != Command::Set {
+            return None;
+        }
+
+        let index = address.checked_sub(self.start_address())? as usize;
+        (index < self.body.len()).then_some(index)
+    }
+}
+
+/// Return the number of address bytes typically used by the model of a packet
+///
+/// Models with extended IDs use 4 bytes, older ones 3 bytes. Some models
+/// deviate from this, so it is only a default.
+pub fn default_address_size(bytes: &[u8]) -> usize {
+    match checksum::roland_command_offset(bytes) {
+        Some(offset) if offset > 4 => 4,
+        _ => 3,
+    }
+}
+
+/// Parse an address in hex notation like `40 00 7F` or `40007F`
+pub fn parse_address(text: &str, address_size: usize) -> Option<u32> {
+    let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.is_ascii() || digits.len() != address_size * 2 {
+        return None;
+    }
+
+    let bytes = (0..address_size)
+        .map(|index| u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    if bytes.iter().any(|byte| *byte >= 0x80) {
+        return None;
+    }
+
+    Some(to_linear(&bytes))
+}
+
+/// Format a linear address in hex notation
+pub fn format_address(address: u32, address_size: usize) -> String {
+    crate::midi::format_hex(&from_linear(address, address_size))
+}
+
+/// Convert a linear value into 7-bit address bytes
+fn from_linear(value: u32, size: usize) -> Vec<u8> {
+    (0..size)
+        .rev()
+        .map(|index| ((value >> (index * 7)) & 0x7F) as u8)
+        .collect()
+}
+
+/// Convert 7-bit address bytes into a linear value
+fn to_linear(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |address, byte| (address << 7) | (*byte & 0x7F) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DT1 packet with three data bytes at address 00 00 7E
+    const PACKET: [u8; 13] = [
+        0xF0, 0x41, 0x10, 0x42, 0x12, 0x00, 0x00, 0x7E, 0x01, 0x02, 0x03, 0x7C, 0xF7,
+    ];
+
+    #[test]
+    fn parse_dt1() {
+        let packet = Packet::parse(&PACKET, 3).unwrap();
+        assert_eq!(packet.command, Command::Set);
+        assert_eq!(packet.address, vec![0x00, 0x00, 0x7E]);
+        assert_eq!(packet.body, vec![0x01, 0x02, 0x03]);
+        assert!(packet.checksum_valid());
+        assert_eq!(packet.to_bytes(), PACKET.to_vec());
+    }
+
+    #[test]
+    fn parse_rejects_short_packet() {
+        assert!(Packet::parse(&PACKET[..8], 3).is_none());
+        // No data bytes left after the address
+        assert!(Packet::parse(&PACKET, 6).is_none());
+    }
+
+    #[test]
+    fn address_crosses_7_bit_boundary() {
+        let packet = Packet::parse(&PACKET, 3).unwrap();
+        assert_eq!(packet.start_address(), 0x7E);
+        assert_eq!(packet.get(0x7E), Some(0x01));
+        assert_eq!(packet.get(0x7F), Some(0x02));
+        // 00 01 00 follows 00 00 7F
+        assert_eq!(
+            packet.get(parse_address("00 01 00", 3).unwrap()),
+            Some(0x03)
+        );
+        assert_eq!(packet.get(parse_address("00 01 01", 3).unwrap()), None);
+        assert_eq!(packet.get(0x7D), None);
+    }
+
+    #[test]
+    fn set_updates_checksum() {
+        let mut packet = Packet::parse(&PACKET, 3).unwrap();
+        assert!(packet.set(0x80, 0x04));
+        assert_eq!(packet.checksum, 0x7B);
+        assert!(packet.checksum_valid());
+        assert!(!packet.set(0x81, 0x04));
+        // Values are limited to 7 bits
+        assert!(packet.set(0x7E, 0xFF));
+        assert_eq!(packet.get(0x7E), Some(0x7F));
+    }
+
+    #[test]
+    fn request_for_dt1() {
+        let request = Packet::parse(&PACKET, 3).unwrap().to_request().unwrap();
+        assert_eq!(
+            request.to_bytes(),
+            vec![
+                0xF0, 0x41, 0x10, 0x42, 0x11, 0x00, 0x00, 0x7E, 0x00, 0x00, 0x03, 0x7F, 0xF7
+            ]
+        );
+        assert!(request.to_request().is_none());
+    }
+
+    #[test]
+    fn address_notation() {
+        assert_eq!(parse_address("40 00 7F", 3), Some(0x10007F));
+        assert_eq!(parse_address("40007F", 3), Some(0x10007F));
+        assert_eq!(parse_address("40 00 80", 3), None);
+        assert_eq!(parse_address("40 00", 3), None);
+        assert_eq!(parse_address("40 0G 00", 3), None);
+        assert_eq!(format_address(0x80, 3), "00 01 00");
+        assert_eq!(format_address(0x10007F, 4), "00 40 00 7F");
+    }
+
+    #[test]
+    fn address_size_by_model_id() {
+        assert_eq!(default_address_size(&PACKET), 3);
+        let extended = [
+            0xF0, 0x41, 0x10, 0x00, 0x00, 0x2B, 0x12, 0x01, 0x00, 0x00, 0x05, 0x7A, 0xF7,
+        ];
+        assert_eq!(default_address_size(&extended), 4);
+    }
+}
@@ -0,0 +1,652 @@
+//! Module containing the network MIDI output via RTP-MIDI (AppleMIDI)
+//!
+//! The application acts as session initiator: it invites the peer on its
+//! control and data port, keeps the clocks synchronized and sends MIDI
+//! commands without recovery journal. SysEx messages that do not fit into a
+//! single packet are segmented as described in RFC 6295, section 3.2.
+
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow};
+
+use crate::midi::{SYSEX_END_BYTE, SYSEX_START_BYTE};
+
+/// Prefix of the identifiers of network ports
+const ID_PREFIX: &str = "rtp:";
+
+/// Control port used if the address contains none
+const DEFAULT_PORT: u16 = 5004;
+
+/// Name announced to the peer
+const SESSION_NAME: &str = "SysEx Drop";
+
+/// Signature starting all session commands
+const SIGNATURE: [u8; 2] = [0xFF, 0xFF];
+
+/// Protocol version of the session commands
+const PROTOCOL_VERSION: u32 = 2;
+
+/// RTP payload type of MIDI packets
+const PAYLOAD_TYPE: u8 = 0x61;
+
+/// Maximum number of SysEx bytes in a single packet
+const SEGMENT_SIZE: usize = 1000;
+
+/// Pause between the packets of a segmented SysEx message
+const SEGMENT_GAP: Duration = Duration::from_millis(1);
+
+/// Number of invitations sent before giving up
+const INVITATION_ATTEMPTS: usize = 3;
+
+/// Time to wait for the reply to an invitation
+const INVITATION_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Interval of the clock synchronization
+const SYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of synchronization intervals without reply after which the peer is considered gone
+const SYNC_MISSES: u32 = 3;
+
+/// Timeout for receiving, also the interval for checking the stop request
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Return if a port identifier belongs to a network port
+pub fn is_rtp_id(id: &str) -> bool {
+    id.starts_with(ID_PREFIX)
+}
+
+/// Return identifier and name of the port for a peer address
+pub fn port(address: &str) -> (String, String) {
+    (
+        format!("{}{}", ID_PREFIX, address),
+        format!("{} (RTP-MIDI)", address),
+    )
+}
+
+/// Return the peer address of a port identifier
+pub fn address(id: &str) -> &str {
+    id.strip_prefix(ID_PREFIX).unwrap_or(id)
+}
+
+/// Normalize a peer address entered by the user, adding the default port if missing
+///
+/// Returns `None` if the address can not be resolved.
+pub fn parse_address(text: &str) -> Option<String> {
+    let text = text.trim();
+    let address = if text.to_socket_addrs().is_ok() {
+        text.to_string()
+    } else {
+        format!("{}:{}", text, DEFAULT_PORT)
+    };
+
+    address.to_socket_addrs().ok()?.next().map(|_| address)
+}
+
+/// Split a message into the commands sent in separate packets
+///
+/// Segments of a SysEx message end with `F0` if more segments follow, further
+/// segments start with `F7`.
+fn segments(message: &[u8]) -> Vec<Vec<u8>> {
+    if message.first() != Some(&SYSEX_START_BYTE) || message.len() <= SEGMENT_SIZE {
+        return vec![message.to_vec()];
+    }
+
+    let data = match message.last() {
+        Some(&SYSEX_END_BYTE) => &message[1..message.len() - 1],
+        _ => &message[1..],
+    };
+    let chunks: Vec<&[u8]> = data.chunks(SEGMENT_SIZE).collect();
+
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let mut segment = Vec::with_capacity(chunk.len() + 2);
+            segment.push(if index == 0 {
+                SYSEX_START_BYTE
+            } else {
+                SYSEX_END_BYTE
+            });
+            segment.extend_from_slice(chunk);
+            segment.push(if index == chunks.len() - 1 {
+                SYSEX_END_BYTE
+            } else {
+                SYSEX_START_BYTE
+            });
+            segment
+        })
+        .collect()
+}
+
+/// Return an RTP packet with a single command and no journal
+fn rtp_packet(sequence: u16, timestamp: u32, ssrc: u32, command: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0x80, PAYLOAD_TYPE];
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(&timestamp.to_be_bytes());
+    packet.extend_from_slice(&ssrc.to_be_bytes());
+
+    // Command section header, the long form holds up to 4095 bytes
+    let length = command.len();
+    if length <= 0x0F {
+        packet.push(length as u8);
+    } else {
+        packet.push(0x80 | ((length >> 8) as u8 & 0x0F));
+        packet.push(length as u8);
+    }
+    packet.extend_from_slice(command);
+
+    packet
+}
+
+/// Session command with name, e.g. an invitation
+fn session_command(command: &[u8; 2], token: u32, ssrc: u32) -> Vec<u8> {
+    let mut packet = Vec::new();
+    packet.extend_from_slice(&SIGNATURE);
+    packet.extend_from_slice(command);
+    packet.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    packet.extend_from_slice(&token.to_be_bytes());
+    packet.extend_from_slice(&ssrc.to_be_bytes());
+    if command != b"BY" {
+        packet.extend_from_slice(SESSION_NAME.as_bytes());
+        packet.push(0);
+    }
+    packet
+}
+
+/// Clock synchronization command
+fn sync_command(ssrc: u32, count: u8, timestamps: [u64; 3]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    packet.extend_from_slice(&SIGNATURE);
+    packet.extend_from_slice(b"CK");
+    packet.extend_from_slice(&ssrc.to_be_bytes());
+    packet.extend_from_slice(&[count, 0, 0, 0]);
+    for timestamp in timestamps {
+        packet.extend_from_slice(&timestamp.to_be_bytes());
+    }
+    packet
+}
+
+/// Return the command and token of a session command from the peer
+fn parse_session_command(packet: &[u8]) -> Option<([u8; 2], u32)> {
+    if packet.get(0..2)? != SIGNATURE {
+        return None;
+    }
+    let command = packet.get(2..4)?.try_into().ok()?;
+    let token = u32::from_be_bytes(packet.get(8..12)?.try_into().ok()?);
+    Some((command, token))
+}
+
+/// Bind two sockets on consecutive ports for control and data
+///
+/// Some implementations expect the data port next to the control port.
+fn bind_sockets(peer: &SocketAddr) -> Result<(UdpSocket, UdpSocket)> {
+    let local: SocketAddr = if peer.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+
+    for _ in 0..10 {
+        let control = UdpSocket::bind(local)?;
+        let mut data_address = control.local_addr()?;
+        let Some(data_port) = data_address.port().checked_add(1) else {
+            continue;
+        };
+        data_address.set_port(data_port);
+        if let Ok(data) = UdpSocket::bind(data_address) {
+            return Ok((control, data));
+        }
+    }
+
+    Err(anyhow!("No free ports for the network MIDI session."))
+}
+
+/// Send an invitation and wait for the acceptance
+fn invite(socket: &UdpSocket, peer: SocketAddr, token: u32, ssrc: u32) -> Result<()> {
+    socket.set_read_timeout(Some(INVITATION_TIMEOUT))?;
+    let mut buffer = [0; 1500];
+
+    for _ in 0..INVITATION_ATTEMPTS {
+        socket.send_to(&session_command(b"IN", token, ssrc), peer)?;
+
+        let deadline = Instant::now() + INVITATION_TIMEOUT;
+        while Instant::now() < deadline {
+            let Ok((size, _)) = socket.recv_from(&mut buffer) else {
+                break;
+            };
+            match parse_session_command(&buffer[..size]) {
+                Some((command, reply_token)) if &command == b"OK" && reply_token == token => {
+                    return Ok(());
+                }
+                Some((command, reply_token)) if &command == b"NO" && reply_token == token => {
+                    return Err(anyhow!("Network MIDI invitation rejected by {}.", peer));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "No reply to network MIDI invitation from {}.",
+        peer
+    ))
+}
+
+/// Return a value that differs between sessions, used for SSRC and token
+fn random_u32() -> u32 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    nanos ^ std::process::id().rotate_left(16)
+}
+
+/// Session with a peer, ended when dropped
+pub struct Session {
+    /// Socket for session commands
+    control: UdpSocket,
+
+    /// Socket for MIDI data
+    data: UdpSocket,
+
+    /// Control address of the peer
+    peer: SocketAddr,
+
+    /// Data address of the peer
+    peer_data: SocketAddr,
+
+    /// Token of the invitation
+    token: u32,
+
+    /// Own synchronization source identifier
+    ssrc: u32,
+
+    /// Start of the session, reference for the timestamps
+    start: Instant,
+
+    /// Sequence number of the next packet
+    sequence: u16,
+
+    /// Session was ended by the peer or the peer stopped responding
+    ended: Arc<AtomicBool>,
+
+    /// Request to stop the session thread
+    stop: Arc<AtomicBool>,
+
+    /// Thread answering synchronization and session commands
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Session {
+    /// Invite the peer at an address like `192.168.1.10:5004`
+    pub fn connect(address: &str) -> Result<Self> {
+        let peer = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("Network MIDI address {} not found.", address))?;
+        let mut peer_data = peer;
+        peer_data.set_port(peer.port().wrapping_add(1));
+
+        let (control, data) = bind_sockets(&peer)?;
+        let token = random_u32();
+        let ssrc = random_u32().rotate_left(8);
+
+        invite(&control, peer, token, ssrc)?;
+        invite(&data, peer_data, token, ssrc)?;
+
+        let mut session = Self {
+            control,
+            data,
+            peer,
+            peer_data,
+            token,
+            ssrc,
+            start: Instant::now(),
+            sequence: 0,
+            ended: Arc::new(AtomicBool::new(false)),
+            stop: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        };
+        session.start_thread()?;
+
+        Ok(session)
+    }
+
+    /// Start the thread answering the peer and synchronizing the clocks
+    fn start_thread(&mut self) -> Result<()> {
+        let control = self.control.try_clone()?;
+        let data = self.data.try_clone()?;
+        control.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        data.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        let peer_data = self.peer_data;
+        let ssrc = self.ssrc;
+        let start = self.start;
+        let ended = self.ended.clone();
+        let stop = self.stop.clone();
+
+        // Timestamps in units of 100 microseconds
+        let now = move || (start.elapsed().as_micros() / 100) as u64;
+
+        self.thread = Some(std::thread::spawn(move || {
+            let mut buffer = [0; 1500];
+            let mut last_sync: Option<Instant> = None;
+            let mut last_reply = Instant::now();
+
+            while !stop.load(Ordering::Relaxed) {
+                if last_reply.elapsed() > SYNC_INTERVAL * SYNC_MISSES {
+                    log::warn!("Network MIDI peer not responding.");
+                    ended.store(true, Ordering::Relaxed);
+                    break;
+                }
+
+                if last_sync.is_none_or(|last_sync| last_sync.elapsed() >= SYNC_INTERVAL) {
+                    data.send_to(&sync_command(ssrc, 0, [now(), 0, 0]), peer_data)
+                        .ok();
+                    last_sync = Some(Instant::now());
+                }
+
+                if let Ok((size, _)) = data.recv_from(&mut buffer) {
+                    let packet = &buffer[..size];
+                    if packet.len() >= 36 && packet[0..4] == [0xFF, 0xFF, b'C', b'K'] {
+                        last_reply = Instant::now();
+                        let timestamp = |index: usize| {
+                            u64::from_be_bytes(
+                                packet[12 + index * 8..20 + index * 8].try_into().unwrap(),
+                            )
+                        };
+                        match packet[8] {
+                            0 => {
+                                data.send_to(
+                                    &sync_command(ssrc, 1, [timestamp(0), now(), 0]),
+                                    peer_data,
+                                )
+                                .ok();
+                            }
+                            1 => {
+                                data.send_to(
+                                    &sync_command(ssrc, 2, [timestamp(0), timestamp(1), now()]),
+                                    peer_data,
+                                )
+                                .ok();
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+
+                if let Ok((size, _)) = control.recv_from(&mut buffer)
+                    && let Some((command, _)) = parse_session_command(&buffer[..size])
+                    && &command == b"BY"
+                {
+                    log::warn!("Network MIDI session ended by the peer.");
+                    ended.store(true, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    /// Return if the session was ended by the peer or the peer stopped responding
+    pub fn ended(&self) -> bool {
+        self.ended.load(Ordering::Relaxed)
+    }
+
+    /// Sends a message, segmenting large SysEx messages
+    pub fn send(&mut self, message: &[u8]) -> Result<()> {
+        if self.ended() {
+            return Err(anyhow!("Network MIDI session ended."));
+        }
+
+        let segments = segments(message);
+        for (index, segment) in segments.iter().enumerate() {
+            if index > 0 {
+                std::thread::sleep(SEGMENT_GAP);
+            }
+            let packet = self.packet(segment);
+            self.data
+                .send_to(&packet, self.peer_data)
+                .map_err(|error| anyhow!("Network MIDI send error: {}", error))?;
+        }
+
+        Ok(())
+    }
+
+    /// Return an RTP packet with a single command and no journal
+    fn packet(&mut self, command: &[u8]) -> Vec<u8> {
+        let timestamp = (self.start.elapsed().as_micros() / 100) as u32;
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+
+        rtp_packet(sequence, timestamp, self.ssrc, command)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+
+        if !self.ended() {
+            self.control
+                .send_to(&session_command(b"BY", self.token, self.ssrc), self.peer)
+                .ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return a SysEx message with the number of data bytes
+    fn sysex(data_size: usize) -> Vec<u8> {
+        let mut message = vec![SYSEX_START_BYTE];
+        message.extend((0..data_size).map(|index| (index % 0x80) as u8));
+        message.push(SYSEX_END_BYTE);
+        message
+    }
+
+    /// Return the message reassembled from segments
+    fn reassemble(segments: &[Vec<u8>]) -> Vec<u8> {
+        let mut message = vec![SYSEX_START_BYTE];
+        for segment in segments {
+            message.extend_from_slice(&segment[1..segment.len() - 1]);
+        }
+        message.push(SYSEX_END_BYTE);
+        message
+    }
+
+    #[test]
+    fn segments_single() {
+        let message = sysex(SEGMENT_SIZE - 2);
+        assert_eq!(message.len(), SEGMENT_SIZE);
+        assert_eq!(segments(&message), vec![message]);
+    }
+
+    #[test]
+    fn segments_boundary() {
+        let message = sysex(SEGMENT_SIZE - 1);
+        assert_eq!(message.len(), SEGMENT_SIZE + 1);
+
+        // All data bytes fit into one segment, which is sent unchanged
+        let segments = segments(&message);
+        assert_eq!(segments, vec![message]);
+    }
+
+    #[test]
+    fn segments_split() {
+        let message = sysex(SEGMENT_SIZE + 1);
+        let segments = segments(&message);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].len(), SEGMENT_SIZE + 2);
+        assert_eq!(segments[0].first(), Some(&SYSEX_START_BYTE));
+        assert_eq!(segments[0].last(), Some(&SYSEX_START_BYTE));
+        assert_eq!(segments[1], vec![SYSEX_END_BYTE, 0x68, SYSEX_END_BYTE]);
+        assert_eq!(reassemble(&segments), message);
+    }
+
+    #[test]
+    fn segments_several() {
+        let message = sysex(SEGMENT_SIZE * 3 + 10);
+        let segments = segments(&message);
+        assert_eq!(segments.len(), 4);
+        assert_eq!(&segments[0][..1], &[SYSEX_START_BYTE]);
+        assert_eq!(segments[0].last(), Some(&SYSEX_START_BYTE));
+        for segment in &segments[1..3] {
+            assert_eq!(segment.len(), SEGMENT_SIZE + 2);
+            assert_eq!(segment.first(), Some(&SYSEX_END_BYTE));
+            assert_eq!(segment.last(), Some(&SYSEX_START_BYTE));
+        }
+        assert_eq!(segments[3].len(), 12);
+        assert_eq!(segments[3].first(), Some(&SYSEX_END_BYTE));
+        assert_eq!(segments[3].last(), Some(&SYSEX_END_BYTE));
+        assert_eq!(reassemble(&segments), message);
+    }
+
+    #[test]
+    fn segments_other_messages() {
+        assert_eq!(segments(&[0x90, 0x40, 0x7F]), vec![vec![0x90, 0x40, 0x7F]]);
+    }
+
+    #[test]
+    fn packet_short_header() {
+        let packet = rtp_packet(0x1234, 0x01020304, 0xAABBCCDD, &[0x90, 0x40, 0x7F]);
+        assert_eq!(
+            packet,
+            vec![
+                0x80,
+                PAYLOAD_TYPE,
+                0x12,
+                0x34,
+                0x01,
+                0x02,
+                0x03,
+                0x04,
+                0xAA,
+                0xBB,
+                0xCC,
+                0xDD,
+                0x03,
+                0x90,
+                0x40,
+                0x7F
+            ]
+        );
+
+        let command = vec![0; 15];
+        assert_eq!(rtp_packet(0, 0, 0, &command)[12], 15);
+    }
+
+    #[test]
+    fn packet_long_header() {
+        let command = vec![0; 16];
+        let packet = rtp_packet(0, 0, 0, &command);
+        assert_eq!(&packet[12..14], &[0x80, 16]);
+        assert_eq!(packet.len(), 14 + 16);
+
+        let command = sysex(SEGMENT_SIZE);
+        let packet = rtp_packet(0, 0, 0, &command);
+        assert_eq!(&packet[12..14], &[0x83, 0xEA]);
+        assert_eq!(&packet[14..], &command[..]);
+    }
+
+    #[test]
+    fn parse_session_commands() {
+        let packet = session_command(b"OK", 0x11223344, 0x55667788);
+        assert_eq!(parse_session_command(&packet), Some((*b"OK", 0x11223344)));
+        assert!(packet.ends_with(b"SysEx Drop\0"));
+
+        let packet = session_command(b"BY", 1, 2);
+        assert_eq!(packet.len(), 16);
+        assert_eq!(parse_session_command(&packet), Some((*b"BY", 1)));
+    }
+
+    #[test]
+    fn parse_session_commands_invalid() {
+        assert_eq!(parse_session_command(&[0x80, 0x61, 0x00, 0x00]), None);
+        assert_eq!(
+            parse_session_command(&[0xFF, 0xFF, b'O', b'K', 0, 0, 0, 2]),
+            None
+        );
+        assert_eq!(parse_session_command(&[]), None);
+    }
+
+    /// Answer invitations on the control and data socket, return the MIDI commands received
+    fn run_peer(control: UdpSocket, data: UdpSocket, commands: usize) -> Vec<Vec<u8>> {
+        let mut buffer = [0; 1500];
+
+        for socket in [&control, &data] {
+            let (size, sender) = socket.recv_from(&mut buffer).unwrap();
+            let (command, token) = parse_session_command(&buffer[..size]).unwrap();
+            assert_eq!(&command, b"IN");
+            socket
+                .send_to(&session_command(b"OK", token, 0x12345678), sender)
+                .unwrap();
+        }
+
+        let mut received = Vec::new();
+        while received.len() < commands {
+            let (size, _) = data.recv_from(&mut buffer).unwrap();
+            let packet = &buffer[..size];
+            // Clock synchronization
+            if packet.starts_with(&SIGNATURE) {
+                continue;
+            }
+            assert_eq!(&packet[..2], &[0x80, PAYLOAD_TYPE]);
+            let (length, offset) = if packet[12] & 0x80 != 0 {
+                (
+                    (((packet[12] & 0x0F) as usize) << 8) | packet[13] as usize,
+                    14,
+                )
+            } else {
+                (packet[12] as usize, 13)
+            };
+            assert_eq!(packet.len(), offset + length);
+            received.push(packet[offset..].to_vec());
+        }
+
+        // The session ends when dropped
+        let (size, _) = control.recv_from(&mut buffer).unwrap();
+        assert_eq!(
+            parse_session_command(&buffer[..size]).map(|(command, _)| command),
+            Some(*b"BY")
+        );
+
+        received
+    }
+
+    #[test]
+    fn session_loopback() {
+        let peer_address: SocketAddr = ([127, 0, 0, 1], 0).into();
+        let (control, data) = bind_sockets(&peer_address).unwrap();
+        for socket in [&control, &data] {
+            socket
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+        }
+        let address = format!("127.0.0.1:{}", control.local_addr().unwrap().port());
+
+        let message = sysex(SEGMENT_SIZE * 2);
+        let expected = segments(&message);
+        let commands = expected.len() + 1;
+        let peer = std::thread::spawn(move || run_peer(control, data, commands));
+
+        let mut session = Session::connect(&address).unwrap();
+        session.send(&[0x90, 0x40, 0x7F]).unwrap();
+        session.send(&message).unwrap();
+        assert!(!session.ended());
+        drop(session);
+
+        let received = peer.join().unwrap();
+        assert_eq!(received[0], vec![0x90, 0x40, 0x7F]);
+        assert_eq!(&received[1..], &expected[..]);
+        assert_eq!(reassemble(&received[1..]), message);
+    }
+}
@@ -0,0 +1,210 @@
+//! Module containing the splitting of files into single packets
+
+use anyhow::{Result, anyhow};
+
+use crate::checksum::{ROLAND_ID, YAMAHA_ID};
+use crate::roland;
+
+/// Minimum number of printable characters at the start of Roland data for a name
+const ROLAND_NAME_MIN_LENGTH: usize = 8;
+
+/// Maximum length of a Roland patch name
+const ROLAND_NAME_MAX_LENGTH: usize = 16;
+
+/// Write each packet to a separate file in a directory
+///
+/// File names are built from the stem, the packet number and the patch name
+/// if one can be decoded. Nothing is written if any of the files already
+/// exists. Returns the number of files written.
+pub fn split(packets: &[Vec<u8>], directory: &std::path::Path, stem: &str) -> Result<usize> {
+    let paths = file_names(packets, stem)
+        .into_iter()
+        .map(|file_name| directory.join(file_name))
+        .collect::<Vec<_>>();
+
+    if let Some(path) = paths.iter().find(|path| path.exists()) {
+        return Err(anyhow!("File {} already exists.", path.display()));
+    }
+
+    for (path, packet) in paths.iter().zip(packets) {
+        std::fs::write(path, packet)?;
+    }
+
+    Ok(packets.len())
+}
+
+/// Return the file names for the packets
+fn file_names(packets: &[Vec<u8>], stem: &str) -> Vec<String> {
+    let digits = packets.len().to_string().len().max(3);
+
+    packets
+        .iter()
+        .enumerate()
+        .map(|(index, packet)| {
+            let mut file_name = format!("{} {:0digits$}", stem, index + 1, digits = digits);
+            if let Some(name) = patch_name(packet) {
+                file_name.push(' ');
+                file_name.push_str(&name);
+            }
+            file_name.push_str(".syx");
+            file_name
+        })
+        .collect()
+}
+
+/// Return the patch name contained in a packet if it can be decoded
+pub fn patch_name(packet: &[u8]) -> Option<String> {
+    let name = match packet.get(1) {
+        Some(&YAMAHA_ID) => yamaha_name(packet),
+        Some(&ROLAND_ID) => roland_name(packet),
+        _ => None,
+    }?;
+
+    let name = sanitize(&name);
+    (!name.is_empty()).then_some(name)
+}
+
+/// Decode the name of a Yamaha single voice dump
+fn yamaha_name(packet: &[u8]) -> Option<String> {
+    // F0 43 0n ff bh bl <data> cs F7
+    let (data_length, name_offset) = match packet.get(3)? {
+        // DX7 VCED
+        0x00 => (155, 145),
+        // DX21/DX27/DX100/TX81Z VCED
+        0x03 => (93, 77),
+        _ => return None,
+    };
+
+    if packet.len() != data_length + 8 {
+        return None;
+    }
+
+    let name = &packet[6 + name_offset..6 + name_offset + 10];
+    Some(String::from_utf8_lossy(name).to_string())
+}
+
+/// Decode the name from the start of a Roland DT1 packet
+///
+/// Many models store the name at the beginning of the common patch parameters,
+/// so printable text at the start of the data is taken as name.
+fn roland_name(packet: &[u8]) -> Option<String> {
+    let packet = roland::Packet::parse(packet, roland::default_address_size(packet))?;
+    if packet.command != roland::Command::Set {
+        return None;
+    }
+
+    let name: String = packet
+        .body
+        .iter()
+        .take(ROLAND_NAME_MAX_LENGTH)
+        .take_while(|byte| (0x20..0x7F).contains(*byte))
+        .map(|byte| *byte as char)
+        .collect();
+
+    (name.len() >= ROLAND_NAME_MIN_LENGTH && name.chars().any(char::is_alphabetic)).then_some(name)
+}
+
+/// Replace characters that are not safe in file names and trim whitespace
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .trim_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Roland DT1 packet with the text at the start of the data
+    fn roland_packet(name: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0xF0, 0x41, 0x10, 0x42, 0x12, 0x10, 0x00, 0x00];
+        packet.extend_from_slice(name);
+        let checksum = crate::checksum::roland_checksum(&packet[5..]);
+        packet.extend_from_slice(&[checksum, 0xF7]);
+        packet
+    }
+
+    /// Return an empty directory for a test
+    fn test_directory(name: &str) -> std::path::PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("sysex-drop-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&directory).ok();
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn names_numbered_and_decoded() {
+        let packets = vec![
+            roland_packet(b"Warm Pad/Strings"),
+            vec![0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7],
+        ];
+        assert_eq!(
+            file_names(&packets, "dump"),
+            vec!["dump 001 Warm Pad_Strings.syx", "dump 002.syx"]
+        );
+    }
+
+    #[test]
+    fn number_width_grows() {
+        let packets = vec![vec![0xF0, 0x01, 0xF7]; 1000];
+        let names = file_names(&packets, "x");
+        assert_eq!(names[0], "x 0001.syx");
+        assert_eq!(names[999], "x 1000.syx");
+    }
+
+    #[test]
+    fn short_roland_text_is_no_name() {
+        assert_eq!(patch_name(&roland_packet(b"Pad\x00\x00\x00")), None);
+        assert_eq!(patch_name(&roland_packet(b"12345678")), None);
+        assert_eq!(
+            patch_name(&roland_packet(b"Bass 1  ")),
+            Some("Bass 1".to_string())
+        );
+    }
+
+    #[test]
+    fn sanitized_names() {
+        assert_eq!(sanitize(" a/b:c* "), "a_b_c_");
+        assert_eq!(sanitize("..name.."), "name");
+    }
+
+    #[test]
+    fn split_writes_each_packet() {
+        let directory = test_directory("split");
+        let packets = vec![vec![0xF0, 0x01, 0xF7], vec![0xF0, 0x02, 0x03, 0xF7]];
+
+        assert_eq!(split(&packets, &directory, "dump").unwrap(), 2);
+        assert_eq!(
+            std::fs::read(directory.join("dump 002.syx")).unwrap(),
+            packets[1]
+        );
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+
+    #[test]
+    fn split_refuses_to_overwrite() {
+        let directory = test_directory("overwrite");
+        let packets = vec![vec![0xF0, 0x01, 0xF7], vec![0xF0, 0x02, 0xF7]];
+        std::fs::write(directory.join("dump 002.syx"), b"keep").unwrap();
+
+        assert!(split(&packets, &directory, "dump").is_err());
+        assert!(!directory.join("dump 001.syx").exists());
+        assert_eq!(
+            std::fs::read(directory.join("dump 002.syx")).unwrap(),
+            b"keep"
+        );
+
+        std::fs::remove_dir_all(&directory).ok();
+    }
+}
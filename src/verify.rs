@@ -0,0 +1,334 @@
+//! Module containing the verification of transfers via dump requests
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use eframe::egui;
+
+use crate::checksum::{ROLAND_ID, YAMAHA_ID};
+use crate::{midi, roland};
+
+/// Time to wait for the first reply after sending the requests
+const FIRST_REPLY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Time without further replies after which capturing is finished
+const REPLY_GAP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Interval for checking the cancel request while capturing
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Size of the report window
+const WINDOW_SIZE: egui::Vec2 = egui::vec2(360.0, 300.0);
+
+/// Result of the verification of a single packet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketResult {
+    /// Reply identical to the sent packet
+    Match,
+
+    /// Reply differs, with offset of the first differing byte
+    Mismatch(usize),
+
+    /// No reply received
+    Missing,
+}
+
+impl std::fmt::Display for PacketResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Match => write!(f, "Match"),
+            Self::Mismatch(offset) => write!(f, "Mismatch at offset {}", offset),
+            Self::Missing => write!(f, "No reply"),
+        }
+    }
+}
+
+/// Return the dump requests for verifying a transfer of the packets
+///
+/// The configured request is used if present, otherwise requests are derived
+/// from the packets for known formats. An empty result means that no request
+/// is available.
+pub fn dump_requests(packets: &[Vec<u8>], configured: Option<Vec<u8>>) -> Vec<Vec<u8>> {
+    if let Some(request) = configured {
+        return vec![request];
+    }
+
+    let mut requests: Vec<Vec<u8>> = Vec::new();
+
+    for packet in packets {
+        let request = match packet.get(1) {
+            Some(&ROLAND_ID) => roland::Packet::parse(packet, roland::default_address_size(packet))
+                .and_then(|packet| packet.to_request())
+                .map(|request| request.to_bytes()),
+            Some(&YAMAHA_ID) if packet.len() > 4 && packet[2] & 0xF0 == 0x00 => {
+                // Bulk dump request with channel and format number of the dump
+                Some(vec![
+                    midi::SYSEX_START_BYTE,
+                    YAMAHA_ID,
+                    0x20 | (packet[2] & 0x0F),
+                    packet[3],
+                    midi::SYSEX_END_BYTE,
+                ])
+            }
+            _ => None,
+        };
+
+        if let Some(request) = request
+            && !requests.contains(&request)
+        {
+            requests.push(request);
+        }
+    }
+
+    requests
+}
+
+/// Send the requests and capture the SysEx replies on the input
+///
+/// Returns `None` if cancelled via the receiver.
+pub fn capture_replies(
+    midi: &Arc<Mutex<midi::MidiConnector>>,
+    requests: &[Vec<u8>],
+    packet_interval: Duration,
+    cancel_receiver: &Receiver<bool>,
+) -> Result<Option<Vec<Vec<u8>>>> {
+    let input_receiver = midi.lock().unwrap().subscribe_input();
+
+    for request in requests {
+        midi::send(midi, request)?;
+        std::thread::sleep(packet_interval);
+
+        if cancel_receiver.try_recv().is_ok() {
+            return Ok(None);
+        }
+    }
+
+    let mut replies = Vec::new();
+    let mut deadline = Instant::now() + FIRST_REPLY_TIMEOUT;
+
+    while Instant::now() < deadline {
+        if cancel_receiver.try_recv().is_ok() {
+            return Ok(None);
+        }
+
+        match input_receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(message) => {
+                if message.data.first() == Some(&midi::SYSEX_START_BYTE) {
+                    replies.push(message.data);
+                    deadline = Instant::now() + REPLY_GAP_TIMEOUT;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    log::info!("Captured {} replies.", replies.len());
+
+    Ok(Some(replies))
+}
+
+/// Compare the sent packets with the replies
+///
+/// A packet matches if an identical reply was received. Otherwise it is
+/// compared to the reply at the same position.
+pub fn compare(sent: &[Vec<u8>], replies: &[Vec<u8>]) -> Vec<PacketResult> {
+    sent.iter()
+        .enumerate()
+        .map(|(index, packet)| {
+            if replies.contains(packet) {
+                PacketResult::Match
+            } else if let Some(reply) = replies.get(index) {
+                let offset = packet
+                    .iter()
+                    .zip(reply)
+                    .position(|(sent, received)| sent != received)
+                    .unwrap_or(packet.len().min(reply.len()));
+                PacketResult::Mismatch(offset)
+            } else {
+                PacketResult::Missing
+            }
+        })
+        .collect()
+}
+
+/// Window showing the results of the last verification
+#[derive(Default)]
+pub struct VerifyReport {
+    /// Window is shown
+    pub open: bool,
+
+    /// Results per packet, empty if no verification was done
+    results: Vec<PacketResult>,
+}
+
+impl VerifyReport {
+    /// Set new results
+    pub fn set_results(&mut self, results: Vec<PacketResult>) {
+        self.results = results;
+    }
+
+    /// Clear the results
+    pub fn clear(&mut self) {
+        self.results.clear();
+        self.open = false;
+    }
+
+    /// Return a summary of the results if present
+    pub fn summary(&self) -> Option<String> {
+        if self.results.is_empty() {
+            return None;
+        }
+
+        let matches = self
+            .results
+            .iter()
+            .filter(|result| **result == PacketResult::Match)
+            .count();
+
+        Some(format!(
+            "Verified {} of {} packets.",
+            matches,
+            self.results.len()
+        ))
+    }
+
+    /// Return if all packets match
+    pub fn all_match(&self) -> bool {
+        self.results
+            .iter()
+            .all(|result| *result == PacketResult::Match)
+    }
+
+    /// Show the window if open
+    pub fn show(&mut self, ctx: &egui::Context) {
+        if !self.open {
+            return;
+        }
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("verify_report"),
+            egui::ViewportBuilder::default()
+                .with_title("Verification Report")
+                .with_inner_size(WINDOW_SIZE),
+            |ui, _class| {
+                if ui.ctx().input(|i| i.viewport().close_requested()) {
+                    self.open = false;
+                }
+
+                egui::CentralPanel::default().show(ui, |ui| {
+                    if let Some(summary) = self.summary() {
+                        ui.label(summary);
+                    }
+                    ui.separator();
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("verify_results")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (index, result) in self.results.iter().enumerate() {
+                                    ui.label(format!("Packet {}", index + 1));
+                                    let color = match result {
+                                        PacketResult::Match => egui::Color32::GREEN,
+                                        _ => egui::Color32::RED,
+                                    };
+                                    ui.label(egui::RichText::new(result.to_string()).color(color));
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                });
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Roland DT1 packet with three data bytes at address 00 00 7E
+    const ROLAND_PACKET: [u8; 13] = [
+        0xF0, 0x41, 0x10, 0x42, 0x12, 0x00, 0x00, 0x7E, 0x01, 0x02, 0x03, 0x7C, 0xF7,
+    ];
+
+    /// Yamaha bulk dump on channel 2 with format number 0x09
+    const YAMAHA_PACKET: [u8; 10] = [0xF0, 0x43, 0x01, 0x09, 0x00, 0x02, 0x10, 0x20, 0x4E, 0xF7];
+
+    #[test]
+    fn roland_request() {
+        let requests = dump_requests(&[ROLAND_PACKET.to_vec()], None);
+        // RQ1 with the address and size of the data, checksum over both
+        assert_eq!(
+            requests,
+            vec![vec![
+                0xF0, 0x41, 0x10, 0x42, 0x11, 0x00, 0x00, 0x7E, 0x00, 0x00, 0x03, 0x7F, 0xF7
+            ]]
+        );
+    }
+
+    #[test]
+    fn yamaha_request() {
+        let requests = dump_requests(&[YAMAHA_PACKET.to_vec()], None);
+        assert_eq!(requests, vec![vec![0xF0, 0x43, 0x21, 0x09, 0xF7]]);
+    }
+
+    #[test]
+    fn requests_without_duplicates() {
+        let packets = vec![YAMAHA_PACKET.to_vec(), YAMAHA_PACKET.to_vec()];
+        assert_eq!(dump_requests(&packets, None).len(), 1);
+    }
+
+    #[test]
+    fn configured_request() {
+        let configured = vec![0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7];
+        assert_eq!(
+            dump_requests(&[ROLAND_PACKET.to_vec()], Some(configured.clone())),
+            vec![configured]
+        );
+    }
+
+    #[test]
+    fn unknown_format() {
+        let packets = vec![vec![0xF0, 0x7D, 0x01, 0x02, 0xF7]];
+        assert!(dump_requests(&packets, None).is_empty());
+    }
+
+    #[test]
+    fn compare_match() {
+        let sent = vec![ROLAND_PACKET.to_vec(), YAMAHA_PACKET.to_vec()];
+        // Replies may arrive in a different order
+        let replies = vec![YAMAHA_PACKET.to_vec(), ROLAND_PACKET.to_vec()];
+        assert_eq!(
+            compare(&sent, &replies),
+            vec![PacketResult::Match, PacketResult::Match]
+        );
+    }
+
+    #[test]
+    fn compare_mismatch() {
+        let mut reply = ROLAND_PACKET.to_vec();
+        reply[9] = 0x05;
+        assert_eq!(
+            compare(&[ROLAND_PACKET.to_vec()], &[reply]),
+            vec![PacketResult::Mismatch(9)]
+        );
+
+        // Shorter reply differs at its end
+        let reply = ROLAND_PACKET[..8].to_vec();
+        assert_eq!(
+            compare(&[ROLAND_PACKET.to_vec()], &[reply]),
+            vec![PacketResult::Mismatch(8)]
+        );
+    }
+
+    #[test]
+    fn compare_missing() {
+        let sent = vec![ROLAND_PACKET.to_vec(), YAMAHA_PACKET.to_vec()];
+        assert_eq!(
+            compare(&sent, &[ROLAND_PACKET.to_vec()]),
+            vec![PacketResult::Match, PacketResult::Missing]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Module containing the watch folder for sending new or modified files automatically
+
+use std::collections::VecDeque;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use anyhow::Result;
+use eframe::egui;
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{DebounceEventResult, Debouncer, new_debouncer};
+
+use crate::Message;
+
+/// Time without further changes before a file is considered complete
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maximum number of lines kept in the event log
+const LOG_LIMIT: usize = 500;
+
+/// Size of the watch folder window
+const WINDOW_SIZE: egui::Vec2 = egui::vec2(520.0, 360.0);
+
+/// Watch folder settings and state
+#[derive(Default, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct FolderWatch {
+    /// Folder to watch
+    folder: Option<std::path::PathBuf>,
+
+    /// Watching enabled flag
+    enabled: bool,
+
+    /// Window is shown
+    #[serde(skip)]
+    pub open: bool,
+
+    /// Active watcher
+    #[serde(skip)]
+    debouncer: Option<Debouncer<RecommendedWatcher>>,
+
+    /// Files waiting for the running transfer to complete
+    #[serde(skip)]
+    queue: VecDeque<std::path::PathBuf>,
+
+    /// File of the running transfer if started from the watch folder
+    #[serde(skip)]
+    sending: Option<std::path::PathBuf>,
+
+    /// Event log lines
+    #[serde(skip)]
+    log: VecDeque<String>,
+}
+
+/// Return if a file should be sent when changed in the watch folder
+pub fn is_watched_file(path: &std::path::Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .map(str::to_lowercase);
+
+    path.is_file() && matches!(extension.as_deref(), Some("syx") | Some("mid"))
+}
+
+impl FolderWatch {
+    /// Start watching if enabled, changed files are passed as messages
+    pub fn start(&mut self, message_sender: &Sender<Message>) {
+        self.debouncer = None;
+
+        if !self.enabled {
+            return;
+        }
+
+        let Some(folder) = self.folder.clone() else {
+            return;
+        };
+
+        match Self::create_debouncer(&folder, message_sender.clone()) {
+            Ok(debouncer) => {
+                self.debouncer = Some(debouncer);
+                self.log(format!("Watching {}", folder.display()));
+            }
+            Err(error) => {
+                self.enabled = false;
+                self.log(format!("Error watching {}: {}", folder.display(), error));
+            }
+        }
+    }
+
+    /// Create a watcher for a folder
+    fn create_debouncer(
+        folder: &std::path::Path,
+        message_sender: Sender<Message>,
+    ) -> Result<Debouncer<RecommendedWatcher>> {
+        let mut debouncer =
+            new_debouncer(
+                DEBOUNCE_TIMEOUT,
+                move |result: DebounceEventResult| match result {
+                    Ok(events) => {
+                        for event in events {
+                            if is_watched_file(&event.path) {
+                                message_sender
+                                    .send(Message::WatchedFileChanged(event.path))
+                                    .ok();
+                            }
+                        }
+                    }
+                    Err(error) => log::error!("Watch folder error: {}", error),
+                },
+            )?;
+
+        debouncer
+            .watcher()
+            .watch(folder, RecursiveMode::NonRecursive)?;
+
+        Ok(debouncer)
+    }
+
+    /// Add a file to the queue if not already contained
+    pub fn enqueue(&mut self, path: &std::path::Path) {
+        if !self.queue.iter().any(|queued| queued == path) {
+            self.queue.push_back(path.to_path_buf());
+            self.log(format!("Queued {}", path.display()));
+        }
+    }
+
+    /// Mark the start of a transfer of a file from the watch folder
+    pub fn transfer_started(&mut self, path: &std::path::Path) {
+        self.log(format!("Sending {}", path.display()));
+        self.sending = Some(path.to_path_buf());
+    }
+
+    /// Log the end of a transfer and return the next file from the queue
+    pub fn transfer_ended(&mut self, outcome: &crate::TransferState) -> Option<std::path::PathBuf> {
+        if let Some(path) = self.sending.take() {
+            self.log(format!("{} {}", outcome, path.display()));
+        }
+
+        self.next_queued()
+    }
+
+    /// Remove and return the next file from the queue
+    pub fn next_queued(&mut self) -> Option<std::path::PathBuf> {
+        self.queue.pop_front()
+    }
+
+    /// Add a line to the event log
+    pub fn log(&mut self, text: String) {
+        log::info!("{}", text);
+        self.log.push_back(format!(
+            "{} {}",
+            chrono::Local::now().format("%H:%M:%S"),
+            text
+        ));
+        if self.log.len() > LOG_LIMIT {
+            self.log.pop_front();
+        }
+    }
+
+    /// Show the window if open
+    pub fn show(&mut self, ctx: &egui::Context, message_sender: &Sender<Message>) {
+        if !self.open {
+            return;
+        }
+
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("watch_window"),
+            egui::ViewportBuilder::default()
+                .with_title("Watch Folder")
+                .with_inner_size(WINDOW_SIZE),
+            |ui, _class| {
+                if ui.ctx().input(|i| i.viewport().close_requested()) {
+                    self.open = false;
+                }
+
+                egui::CentralPanel::default().show(ui, |ui| self.ui(ui, message_sender));
+            },
+        );
+    }
+
+    /// Contents of the watch folder window
+    fn ui(&mut self, ui: &mut egui::Ui, message_sender: &Sender<Message>) {
+        ui.horizontal(|ui| {
+            ui.label("Folder:");
+            let folder = self
+                .folder
+                .as_ref()
+                .map(|folder| folder.display().to_string())
+                .unwrap_or_else(|| "None".to_string());
+            ui.label(folder);
+            if ui.button("Choose…").clicked() {
+                let mut dialog = rfd::FileDialog::new();
+                if let Some(folder) = &self.folder {
+                    dialog = dialog.set_directory(folder);
+                }
+                if let Some(folder) = dialog.pick_folder() {
+                    self.folder = Some(folder);
+                    self.start(message_sender);
+                }
+            }
+        });
+
+        ui.add_enabled_ui(self.folder.is_some(), |ui| {
+            if ui
+                .checkbox(&mut self.enabled, "Send new and modified files")
+                .on_hover_text("Files are sent to the selected device\nafter being validated")
+                .changed()
+            {
+                if !self.enabled {
+                    self.log("Watching stopped".to_string());
+                }
+                self.start(message_sender);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Queued files: {}", self.queue.len()));
+            if ui
+                .add_enabled(!self.queue.is_empty(), egui::Button::new("Clear queue"))
+                .clicked()
+            {
+                self.queue.clear();
+            }
+            if ui.button("Clear log").clicked() {
+                self.log.clear();
+            }
+        });
+
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for line in &self.log {
+                    ui.label(line);
+                }
+            });
+    }
+}